@@ -0,0 +1,407 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::utils::LogEntry;
+
+/// nginx's predefined `combined` format, also Apache's `combined`.
+const COMBINED: &str = r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent "$http_referer" "$http_user_agent""#;
+/// The Common Log Format.
+const COMMON: &str =
+    r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent"#;
+
+/// Variables stored as integers.
+const INT_VARS: &[&str] = &[
+    "status",
+    "body_bytes_sent",
+    "bytes_sent",
+    "request_length",
+    "connection",
+    "connection_requests",
+    "server_port",
+    "upstream_status",
+    "upstream_bytes_received",
+];
+/// Variables stored as floats, in seconds.
+const FLOAT_VARS: &[&str] = &[
+    "request_time",
+    "upstream_response_time",
+    "upstream_connect_time",
+    "upstream_header_time",
+];
+
+lazy_static! {
+    /// Formats tried when a request names none: `LOGLITE_NGINX_LOG_FORMAT`, or `combined` then
+    /// `common`, both allowing the extensions described at [`AccessLogFormat::parse_line`].
+    static ref DEFAULT_FORMATS: Vec<AccessLogFormat> = {
+        let configured = std::env::var("LOGLITE_NGINX_LOG_FORMAT")
+            .ok()
+            .filter(|f| !f.trim().is_empty());
+        match configured.map(|f| AccessLogFormat::compile(&f)) {
+            Some(Ok(format)) => vec![format],
+            Some(Err(e)) => {
+                tracing::warn!("Ignoring LOGLITE_NGINX_LOG_FORMAT: {}", e);
+                builtin_formats()
+            }
+            None => builtin_formats(),
+        }
+    };
+}
+
+fn builtin_formats() -> Vec<AccessLogFormat> {
+    [COMBINED, COMMON]
+        .iter()
+        .map(|f| AccessLogFormat::build(f, true).expect("built-in access log format"))
+        .collect()
+}
+
+/// Formats used for lines without an explicit one.
+pub fn default_formats() -> &'static [AccessLogFormat] {
+    &DEFAULT_FORMATS
+}
+
+enum Token {
+    Literal(String),
+    Var(String),
+}
+
+/// An nginx `log_format` or Apache `LogFormat` string compiled to a regex.
+#[derive(Debug)]
+pub struct AccessLogFormat {
+    regex: Regex,
+    /// Variable captured by group `__v<i>`.
+    vars: Vec<String>,
+}
+
+impl AccessLogFormat {
+    /// Compile a format string.
+    ///
+    /// nginx variables are written `$name` or `${name}`; Apache directives such as `%h`,
+    /// `%>s` or `%{User-Agent}i` are mapped to the matching nginx variable, so both produce
+    /// the same fields.
+    pub fn compile(format: &str) -> Result<Self, String> {
+        Self::build(format, false)
+    }
+
+    fn build(format: &str, extensions: bool) -> Result<Self, String> {
+        let tokens = tokenize(format)?;
+        let mut pattern = String::from("^");
+        let mut vars = Vec::new();
+
+        for (i, token) in tokens.iter().enumerate() {
+            let var = match token {
+                Token::Literal(text) => {
+                    pattern.push_str(&regex::escape(text));
+                    continue;
+                }
+                Token::Var(var) => var,
+            };
+            let quoted = matches!(tokens.get(i.wrapping_sub(1)), Some(Token::Literal(t)) if t.ends_with('"'));
+            let value = match tokens.get(i + 1) {
+                Some(Token::Literal(next)) => {
+                    let stop = next.chars().next().unwrap_or(' ');
+                    if quoted && stop == '"' {
+                        r#"(?:[^"\\]|\\.)*"#.to_string()
+                    } else if var.starts_with("upstream_") && stop == ' ' {
+                        // One value per upstream tried, as in "0.002, 0.013 : 0.001"
+                        r"[^ ,]*(?:(?:, | : )[^ ,]*)*".to_string()
+                    } else {
+                        format!("[^{}]*", regex::escape(&stop.to_string()))
+                    }
+                }
+                Some(Token::Var(_)) => ".*?".to_string(),
+                None if extensions => r"\S*".to_string(),
+                None => ".*".to_string(),
+            };
+            pattern.push_str(&format!("(?P<__v{}>{})", vars.len(), value));
+            vars.push(var.clone());
+        }
+        if extensions {
+            pattern.push_str(r"(?:\s+(?P<__rest>.*?))?");
+        }
+        pattern.push_str(r"\s*$");
+
+        if vars.is_empty() {
+            return Err("format has no variables".to_string());
+        }
+        let regex = Regex::new(&pattern).map_err(|e| format!("invalid format: {}", e))?;
+        Ok(Self { regex, vars })
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.regex.is_match(line.trim())
+    }
+
+    /// Parse one line into typed fields named after the format's variables.
+    ///
+    /// `$request` is split into `method`, `path` and `protocol`, `$time_local`,
+    /// `$time_iso8601` or `$msec` becomes the timestamp, and `-` values are left out. The level
+    /// follows the status class: `ERROR` for 5xx, `WARN` for 4xx and `INFO` otherwise.
+    ///
+    /// The built-in formats also accept trailing values: `key=value` pairs such as
+    /// `rt=0.120 urt="0.118"`, or bare numbers read as `request_time` then
+    /// `upstream_response_time`.
+    pub fn parse_line(&self, line: &str) -> Option<LogEntry> {
+        let line = line.trim();
+        let caps = self.regex.captures(line)?;
+        let mut timestamp = None;
+        let mut fields = Map::new();
+        for (i, var) in self.vars.iter().enumerate() {
+            if let Some(value) = caps.name(&format!("__v{}", i)) {
+                set_var(&mut fields, &mut timestamp, var, value.as_str());
+            }
+        }
+        if let Some(rest) = caps.name("__rest") {
+            parse_extensions(&mut fields, rest.as_str());
+        }
+
+        let level = match fields.get("status").and_then(Value::as_i64) {
+            Some(500..=599) => "ERROR",
+            Some(400..=499) => "WARN",
+            _ => "INFO",
+        };
+        Some(LogEntry {
+            timestamp: timestamp
+                .unwrap_or_else(|| Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())),
+            level: level.to_string(),
+            message: line.to_string(),
+            stacktrace: None,
+            fields: Value::Object(fields),
+        })
+    }
+}
+
+fn tokenize(format: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                push_var(&mut tokens, &mut literal, name);
+            }
+            '$' if chars
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_') =>
+            {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                push_var(&mut tokens, &mut literal, name);
+            }
+            '%' => {
+                while chars.peek().is_some_and(|c| *c == '>' || *c == '<') {
+                    chars.next();
+                }
+                let arg: Option<String> = (chars.peek() == Some(&'{'))
+                    .then(|| chars.by_ref().skip(1).take_while(|&c| c != '}').collect());
+                let directive = chars.next().ok_or("format ends with %")?;
+                if directive == '%' {
+                    literal.push('%');
+                    continue;
+                }
+                if directive == 't' && arg.is_none() {
+                    // Written as "[10/Oct/2000:13:55:36 -0700]"
+                    literal.push('[');
+                    push_var(&mut tokens, &mut literal, "time_local".to_string());
+                    literal.push(']');
+                    continue;
+                }
+                let var = apache_var(directive, arg.as_deref())
+                    .ok_or_else(|| format!("unsupported directive %{}", directive))?;
+                push_var(&mut tokens, &mut literal, var);
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    Ok(tokens)
+}
+
+fn push_var(tokens: &mut Vec<Token>, literal: &mut String, var: String) {
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(std::mem::take(literal)));
+    }
+    tokens.push(Token::Var(var));
+}
+
+/// The nginx variable matching an Apache `LogFormat` directive.
+fn apache_var(directive: char, arg: Option<&str>) -> Option<String> {
+    let header = |prefix: &str| {
+        arg.map(|name| format!("{}{}", prefix, name.to_lowercase().replace('-', "_")))
+    };
+    let var = match directive {
+        'a' | 'h' => "remote_addr",
+        'l' => "remote_ident",
+        'u' => "remote_user",
+        'r' => "request",
+        's' => "status",
+        'b' | 'B' => "body_bytes_sent",
+        'O' => "bytes_sent",
+        'I' => "request_length",
+        'D' => "request_time_us",
+        'T' => "request_time",
+        'v' => "server_name",
+        'V' => "host",
+        'H' => "server_protocol",
+        'm' => "request_method",
+        'U' => "uri",
+        'q' => "args",
+        'p' => "server_port",
+        'i' => return header("http_"),
+        'o' => return header("sent_http_"),
+        _ => return None,
+    };
+    Some(var.to_string())
+}
+
+/// Store `value` of `var`, typed, in `fields`, or as the timestamp.
+fn set_var(
+    fields: &mut Map<String, Value>,
+    timestamp: &mut Option<DateTime<FixedOffset>>,
+    var: &str,
+    value: &str,
+) {
+    if value.is_empty() || value == "-" {
+        return;
+    }
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let parsed_ts = match var {
+        "time_local" => DateTime::parse_from_str(value, "%d/%b/%Y:%H:%M:%S %z").ok(),
+        "time_iso8601" => DateTime::parse_from_rfc3339(value).ok(),
+        "msec" => value.parse::<f64>().ok().and_then(|secs| {
+            DateTime::from_timestamp(secs.trunc() as i64, (secs.fract() * 1e9) as u32)
+                .map(|dt| dt.fixed_offset())
+        }),
+        "request" => {
+            let parts: Vec<&str> = value.split(' ').collect();
+            match parts.as_slice() {
+                [method, path, protocol] => {
+                    fields.insert("method".to_string(), Value::from(*method));
+                    fields.insert("path".to_string(), Value::from(*path));
+                    fields.insert("protocol".to_string(), Value::from(*protocol));
+                }
+                [method, path] => {
+                    fields.insert("method".to_string(), Value::from(*method));
+                    fields.insert("path".to_string(), Value::from(*path));
+                }
+                // Garbage such as a TLS handshake sent to a plain HTTP port
+                _ => {
+                    fields.insert("request".to_string(), Value::from(value));
+                }
+            }
+            return;
+        }
+        "request_time_us" => {
+            if let Ok(us) = value.parse::<f64>() {
+                fields.insert("request_time".to_string(), Value::from(us / 1e6));
+            }
+            return;
+        }
+        _ => {
+            fields.insert(var.to_string(), typed(var, value));
+            return;
+        }
+    };
+    match parsed_ts {
+        Some(ts) if timestamp.is_none() => *timestamp = Some(ts.with_timezone(&utc)),
+        Some(_) => {}
+        None => {
+            fields.insert(var.to_string(), Value::from(value));
+        }
+    }
+}
+
+/// `value` as a number for numeric variables, falling back to a string.
+fn typed(var: &str, value: &str) -> Value {
+    let number = if INT_VARS.contains(&var) {
+        value.parse::<i64>().ok().map(Value::from)
+    } else if FLOAT_VARS.contains(&var) {
+        value.parse::<f64>().ok().map(Value::from)
+    } else {
+        None
+    };
+    number.unwrap_or_else(|| Value::from(value))
+}
+
+/// Read values appended after a built-in format.
+fn parse_extensions(fields: &mut Map<String, Value>, rest: &str) {
+    let mut positional = ["request_time", "upstream_response_time"].into_iter();
+    for token in split_quoted(rest) {
+        match token.split_once('=') {
+            Some((key, value)) if !key.is_empty() && !key.starts_with('"') => {
+                let var = match key {
+                    "rt" => "request_time",
+                    "urt" => "upstream_response_time",
+                    "uct" => "upstream_connect_time",
+                    "uht" => "upstream_header_time",
+                    "ua" | "upstream" => "upstream_addr",
+                    "us" => "upstream_status",
+                    other => other,
+                };
+                let value = value.trim_matches('"');
+                if !value.is_empty() && value != "-" {
+                    fields.insert(var.to_string(), typed(var, value));
+                }
+            }
+            _ => {
+                let value = token.trim_matches('"');
+                if value == "-" || value.parse::<f64>().is_ok() {
+                    if let Some(var) = positional.next() {
+                        if value != "-" {
+                            fields.insert(var.to_string(), typed(var, value));
+                        }
+                    }
+                } else if is_address_list(value) && !fields.contains_key("http_x_forwarded_for") {
+                    // nginx's default "main" format ends with "$http_x_forwarded_for"
+                    fields.insert("http_x_forwarded_for".to_string(), Value::from(value));
+                }
+            }
+        }
+    }
+}
+
+/// Split on whitespace outside double quotes.
+fn split_quoted(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut in_quotes = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                start.get_or_insert(i);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if let Some(s) = start.take() {
+                    tokens.push(&text[s..i]);
+                }
+            }
+            _ => {
+                start.get_or_insert(i);
+            }
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&text[s..]);
+    }
+    tokens
+}
+
+fn is_address_list(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() || matches!(c, '.' | ':' | ',' | ' '))
+}
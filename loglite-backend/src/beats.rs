@@ -0,0 +1,308 @@
+use anyhow::{bail, Result};
+use flate2::read::ZlibDecoder;
+use rocket::http::Status;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::db::DEFAULT_APP;
+use crate::elastic::{document_to_event, lookup_str};
+use crate::handlers::{ingest_resolved, resolve_app};
+use crate::listener::parse_port_list;
+use crate::models::IngestEvent;
+use crate::state::AppState;
+
+/// Largest single frame (or decompressed frame batch) accepted from a client.
+const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+
+/// Beats listener configuration, read from `LOGLITE_BEATS_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct BeatsConfig {
+    pub bind: String,
+    pub ports: Vec<u16>,
+    pub app_id: String,
+}
+
+impl BeatsConfig {
+    /// Load the listener configuration from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            bind: std::env::var("LOGLITE_BEATS_BIND").unwrap_or_else(|_| "0.0.0.0".to_string()),
+            ports: parse_port_list(&std::env::var("LOGLITE_BEATS_PORTS").unwrap_or_default()),
+            app_id: std::env::var("LOGLITE_BEATS_APP_ID")
+                .unwrap_or_else(|_| DEFAULT_APP.to_string()),
+        }
+    }
+}
+
+/// A decoded Lumberjack frame.
+#[derive(Debug, Clone)]
+pub enum Frame {
+    /// `W`: number of events the client sends before waiting for an ACK.
+    Window(u32),
+    /// `J` (JSON) or `D` (key/value) data frame.
+    Event { seq: u32, doc: serde_json::Value },
+}
+
+fn read_u32(buf: &[u8], at: usize) -> Option<u32> {
+    let bytes = buf.get(at..at + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Bytes `[at, at + len)` of `buf`, or `None` when they have not all arrived yet.
+fn take(buf: &[u8], at: usize, len: usize) -> Result<Option<&[u8]>> {
+    if len > MAX_FRAME_BYTES {
+        bail!("frame of {} bytes exceeds limit", len);
+    }
+    Ok(buf.get(at..at + len))
+}
+
+/// Parse one frame from the front of `buf`.
+///
+/// Returns the decoded frames with the number of bytes consumed, or `None` if `buf` does not
+/// yet hold a complete frame. A compressed (`C`) frame expands into the frames it contains.
+pub fn parse_frame(buf: &[u8]) -> Result<Option<(Vec<Frame>, usize)>> {
+    let (Some(&version), Some(&kind)) = (buf.first(), buf.get(1)) else {
+        return Ok(None);
+    };
+    if version != b'1' && version != b'2' {
+        bail!("unsupported lumberjack version {:?}", version as char);
+    }
+
+    match kind {
+        b'W' => Ok(read_u32(buf, 2).map(|size| (vec![Frame::Window(size)], 6))),
+        b'J' => {
+            let (Some(seq), Some(len)) = (read_u32(buf, 2), read_u32(buf, 6)) else {
+                return Ok(None);
+            };
+            let Some(payload) = take(buf, 10, len as usize)? else {
+                return Ok(None);
+            };
+            let doc = serde_json::from_slice(payload)?;
+            Ok(Some((vec![Frame::Event { seq, doc }], 10 + len as usize)))
+        }
+        b'D' => {
+            let (Some(seq), Some(count)) = (read_u32(buf, 2), read_u32(buf, 6)) else {
+                return Ok(None);
+            };
+            let mut at = 10;
+            let mut doc = serde_json::Map::new();
+            for _ in 0..count {
+                let Some(key_len) = read_u32(buf, at) else {
+                    return Ok(None);
+                };
+                let Some(key) = take(buf, at + 4, key_len as usize)? else {
+                    return Ok(None);
+                };
+                at += 4 + key_len as usize;
+                let Some(value_len) = read_u32(buf, at) else {
+                    return Ok(None);
+                };
+                let Some(value) = take(buf, at + 4, value_len as usize)? else {
+                    return Ok(None);
+                };
+                at += 4 + value_len as usize;
+                doc.insert(
+                    String::from_utf8_lossy(key).into_owned(),
+                    serde_json::Value::String(String::from_utf8_lossy(value).into_owned()),
+                );
+            }
+            let doc = serde_json::Value::Object(doc);
+            Ok(Some((vec![Frame::Event { seq, doc }], at)))
+        }
+        b'C' => {
+            let Some(len) = read_u32(buf, 2) else {
+                return Ok(None);
+            };
+            let Some(payload) = take(buf, 6, len as usize)? else {
+                return Ok(None);
+            };
+            let mut inflated = Vec::new();
+            ZlibDecoder::new(payload)
+                .take(MAX_FRAME_BYTES as u64 + 1)
+                .read_to_end(&mut inflated)?;
+            if inflated.len() > MAX_FRAME_BYTES {
+                bail!("compressed frame expands beyond {} bytes", MAX_FRAME_BYTES);
+            }
+
+            let mut frames = Vec::new();
+            let mut at = 0;
+            while at < inflated.len() {
+                match parse_frame(&inflated[at..])? {
+                    Some((inner, used)) => {
+                        frames.extend(inner);
+                        at += used;
+                    }
+                    None => bail!("truncated frame inside compressed frame"),
+                }
+            }
+            Ok(Some((frames, 6 + len as usize)))
+        }
+        other => bail!("unknown lumberjack frame type {:?}", other as char),
+    }
+}
+
+/// Encode an ACK frame for `seq`.
+fn ack_frame(seq: u32) -> [u8; 6] {
+    let s = seq.to_be_bytes();
+    [b'2', b'A', s[0], s[1], s[2], s[3]]
+}
+
+/// Background task running the Beats (Lumberjack v2) listener for Filebeat `output.logstash`.
+///
+/// Only started when `LOGLITE_BEATS_PORTS` is set. Events go to the app named by the event's
+/// `fields.app`, subject to the unknown-app policy, otherwise to `LOGLITE_BEATS_APP_ID`. TLS is not
+/// supported.
+pub async fn beats_listener_loop(state: Arc<AppState>) {
+    let config = Arc::new(BeatsConfig::from_env());
+
+    for &port in &config.ports {
+        let addr = format!("{}:{}", config.bind, port);
+        let listener = match TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("Failed to bind beats listener {}: {}", addr, e);
+                continue;
+            }
+        };
+        tracing::info!("Beats listener on {}", addr);
+
+        let state = state.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let state = state.clone();
+                        let config = config.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = handle_connection(stream, peer, &state, &config).await {
+                                tracing::debug!("Beats connection {} closed: {}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) => tracing::error!("Beats accept failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// Read frames from one client, persisting each window before ACKing its last sequence.
+///
+/// If persisting fails the connection is dropped without an ACK, so the client resends the
+/// window.
+async fn handle_connection(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    state: &AppState,
+    config: &BeatsConfig,
+) -> Result<()> {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut read_buf = vec![0u8; 64 * 1024];
+    let mut window: usize = 0;
+    let mut pending: Vec<(u32, serde_json::Value)> = Vec::new();
+
+    loop {
+        while let Some((frames, used)) = parse_frame(&buf)? {
+            buf.drain(..used);
+            for frame in frames {
+                match frame {
+                    Frame::Window(size) => window = size as usize,
+                    Frame::Event { seq, doc } => pending.push((seq, doc)),
+                }
+            }
+
+            if !pending.is_empty() && pending.len() >= window {
+                let last_seq = pending.last().map(|(seq, _)| *seq).unwrap_or(0);
+                persist_window(state, config, &pending).await?;
+                pending.clear();
+                stream.write_all(&ack_frame(last_seq)).await?;
+            }
+        }
+
+        let n = stream.read(&mut read_buf).await?;
+        if n == 0 {
+            if !pending.is_empty() {
+                tracing::debug!(
+                    "Beats client {} disconnected with {} unacknowledged events",
+                    peer,
+                    pending.len()
+                );
+            }
+            return Ok(());
+        }
+        buf.extend_from_slice(&read_buf[..n]);
+    }
+}
+
+/// Idempotency key of a Beats document: its `@metadata._id` when the shipper sets one,
+/// otherwise a hash of the document, so a window resent after a failed ACK is stored once.
+fn document_key(doc: &serde_json::Value) -> String {
+    match lookup_str(doc, "@metadata._id") {
+        Some(id) => format!("beats:{}", id),
+        None => format!(
+            "beats:{}",
+            hex::encode(Sha256::digest(doc.to_string().as_bytes()))
+        ),
+    }
+}
+
+/// Store a window of events, resolving every app before anything is written.
+async fn persist_window(
+    state: &AppState,
+    config: &BeatsConfig,
+    window: &[(u32, serde_json::Value)],
+) -> Result<()> {
+    let mut by_app: HashMap<String, Vec<IngestEvent>> = HashMap::new();
+
+    for (seq, doc) in window {
+        let mut event = match document_to_event(doc, "beats") {
+            Ok(event) => event,
+            Err(reason) => {
+                tracing::debug!("Dropping beats event {}: {}", seq, reason);
+                continue;
+            }
+        };
+        event.sourcetype = Some(
+            lookup_str(doc, "@metadata.beat")
+                .unwrap_or("beats")
+                .to_string(),
+        );
+        event.idempotency_key = Some(document_key(doc));
+
+        let app_id = lookup_str(doc, "fields.app").unwrap_or(&config.app_id);
+        by_app.entry(app_id.to_string()).or_default().push(event);
+    }
+
+    let mut resolved = Vec::with_capacity(by_app.len());
+    for (app_id, events) in &by_app {
+        // A rejected app would be rejected again on every resend, so its events are dropped.
+        match resolve_app(state, app_id).await {
+            Ok(app) => resolved.push((app, events)),
+            Err(status) if status == Status::NotFound => tracing::warn!(
+                "Dropping {} beats events for unknown app {}",
+                events.len(),
+                app_id
+            ),
+            Err(status) => {
+                return Err(anyhow::anyhow!(
+                    "failed to resolve app {}: {}",
+                    app_id,
+                    status
+                ))
+            }
+        }
+    }
+
+    for (app, events) in &resolved {
+        ingest_resolved(state, app, events)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to ingest beats events: {}", e))?;
+    }
+    Ok(())
+}
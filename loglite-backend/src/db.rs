@@ -1,9 +1,16 @@
 use anyhow::Result;
+use chrono::{FixedOffset, Utc};
 use sea_orm::{
-    sea_query::TableCreateStatement, ConnectionTrait, Database, DatabaseConnection, Schema,
+    sea_query::{ColumnDef, OnConflict, Table, TableAlterStatement, TableCreateStatement},
+    ConnectionTrait, Database, DatabaseConnection, EntityTrait, Schema, Set,
 };
 
 use crate::entities::prelude::*;
+use crate::utils::generate_app_id;
+
+/// App that receives events sent without an app, unless an entry point is configured with
+/// another one.
+pub const DEFAULT_APP: &str = "default";
 
 /// Initialize database connection and create tables if needed.
 pub async fn init_db(db_url: &str) -> Result<DatabaseConnection> {
@@ -28,11 +35,75 @@ pub async fn init_db(db_url: &str) -> Result<DatabaseConnection> {
             .create_table_from_entity(Event)
             .if_not_exists()
             .to_owned(),
+        schema
+            .create_table_from_entity(IngestKey)
+            .if_not_exists()
+            .to_owned(),
+        schema
+            .create_table_from_entity(Pipeline)
+            .if_not_exists()
+            .to_owned(),
+        schema
+            .create_table_from_entity(RedactionRule)
+            .if_not_exists()
+            .to_owned(),
+        schema
+            .create_table_from_entity(FilterRule)
+            .if_not_exists()
+            .to_owned(),
+        schema
+            .create_table_from_entity(AppQuota)
+            .if_not_exists()
+            .to_owned(),
+        schema
+            .create_table_from_entity(Parser)
+            .if_not_exists()
+            .to_owned(),
     ];
 
     for stmt in stmts {
         db.execute(backend.build(&stmt)).await?;
     }
 
+    // Columns added after a table was first created.
+    let alters: Vec<TableAlterStatement> = vec![
+        Table::alter()
+            .table(AppSource)
+            .add_column_if_not_exists(
+                ColumnDef::new(crate::entities::app_sources::Column::Parser).string(),
+            )
+            .to_owned(),
+        Table::alter()
+            .table(TailOffset)
+            .add_column_if_not_exists(
+                ColumnDef::new(crate::entities::tail_offsets::Column::Generation)
+                    .big_integer()
+                    .not_null()
+                    .default(0),
+            )
+            .to_owned(),
+    ];
+
+    for stmt in alters {
+        db.execute(backend.build(&stmt)).await?;
+    }
+
+    // Provision the default app, so events sent without one are accepted under the `reject`
+    // unknown-app policy.
+    let default_app = crate::entities::apps::ActiveModel {
+        app_id: Set(generate_app_id(DEFAULT_APP)),
+        name: Set(DEFAULT_APP.to_string()),
+        created_at: Set(Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())),
+    };
+    App::insert(default_app)
+        .on_conflict(
+            OnConflict::column(crate::entities::apps::Column::AppId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(&db)
+        .await?;
+
     Ok(db)
 }
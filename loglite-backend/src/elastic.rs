@@ -0,0 +1,160 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use sea_orm::prelude::DateTimeWithTimeZone;
+
+use crate::models::IngestEvent;
+use crate::utils::{level_to_severity, parse_timestamp};
+
+lazy_static! {
+    // Daily index suffixes such as "logstash-2024.02.09" or "logs-2024-02-09".
+    static ref DATED_INDEX_RE: Regex = Regex::new(r"-\d{4}[.\-]\d{2}[.\-]\d{2}$").unwrap();
+}
+
+/// One operation from an Elasticsearch `_bulk` request body.
+#[derive(Debug, Clone)]
+pub struct BulkOperation {
+    /// Action name: `index`, `create`, `update` or `delete`.
+    pub action: String,
+    pub index: Option<String>,
+    pub id: Option<String>,
+    pub document: Option<serde_json::Value>,
+}
+
+/// Parse an NDJSON `_bulk` body into action/document pairs.
+///
+/// `delete` actions carry no document line; every other action consumes the next line.
+/// A malformed action line fails the whole request, as in Elasticsearch.
+pub fn parse_bulk_body(body: &str) -> Result<Vec<BulkOperation>> {
+    let mut ops = Vec::new();
+    let mut lines = body.lines().filter(|l| !l.trim().is_empty());
+
+    while let Some(line) = lines.next() {
+        let action: serde_json::Value = serde_json::from_str(line)?;
+        let (name, meta) = action
+            .as_object()
+            .filter(|obj| obj.len() == 1)
+            .and_then(|obj| obj.iter().next())
+            .ok_or_else(|| anyhow!("malformed action/metadata line"))?;
+
+        let meta_str = |key: &str| match meta.get(key) {
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        };
+
+        let document = match name.as_str() {
+            "delete" => None,
+            "index" | "create" | "update" => {
+                let doc_line = lines
+                    .next()
+                    .ok_or_else(|| anyhow!("action [{}] is missing its document", name))?;
+                Some(serde_json::from_str(doc_line)?)
+            }
+            other => bail!("unknown bulk action [{}]", other),
+        };
+
+        ops.push(BulkOperation {
+            action: name.clone(),
+            index: meta_str("_index"),
+            id: meta_str("_id"),
+            document,
+        });
+    }
+
+    Ok(ops)
+}
+
+/// App name an index maps to, with any daily date suffix removed so rolling indices share
+/// one app.
+pub fn index_app_name(index: &str) -> &str {
+    match DATED_INDEX_RE.find(index) {
+        Some(m) if m.start() > 0 => &index[..m.start()],
+        _ => index,
+    }
+}
+
+/// Look up a value by ECS path, accepting both nested objects and dotted keys
+/// (`{"log": {"level": "warn"}}` and `{"log.level": "warn"}`).
+fn lookup<'a>(doc: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    if let Some(v) = doc.get(path) {
+        return Some(v);
+    }
+    let (head, rest) = path.split_once('.')?;
+    lookup(doc.get(head)?, rest)
+}
+
+/// String value at an ECS path, ignoring empty strings.
+pub fn lookup_str<'a>(doc: &'a serde_json::Value, path: &str) -> Option<&'a str> {
+    lookup(doc, path)
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+}
+
+fn default_ts() -> DateTimeWithTimeZone {
+    Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+/// Map an ECS-style document (bulk item or Beats event) onto an ingest event.
+///
+/// `@timestamp`, `message`, `host` (or ECS `host.name`) and `log.level` fill the matching
+/// columns, and `log.file.path` becomes the source, falling back to `default_source`. The rest
+/// of the document is kept in `fields`. Errors are returned as a human-readable reason.
+pub fn document_to_event(
+    doc: &serde_json::Value,
+    default_source: &str,
+) -> Result<IngestEvent, String> {
+    if !doc.is_object() {
+        return Err("document must be a JSON object".to_string());
+    }
+
+    let ts = match doc.get("@timestamp") {
+        None | Some(serde_json::Value::Null) => default_ts(),
+        Some(serde_json::Value::String(s)) => {
+            parse_timestamp(s).ok_or_else(|| format!("failed to parse [@timestamp]: {}", s))?
+        }
+        Some(serde_json::Value::Number(n)) => n
+            .as_i64()
+            .and_then(DateTime::from_timestamp_millis)
+            .map(|utc| utc.with_timezone(&FixedOffset::east_opt(0).unwrap()))
+            .ok_or_else(|| format!("failed to parse [@timestamp]: {}", n))?,
+        Some(other) => return Err(format!("failed to parse [@timestamp]: {}", other)),
+    };
+
+    let message = lookup_str(doc, "message")
+        .or_else(|| lookup_str(doc, "log"))
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| doc.to_string());
+
+    let host = lookup_str(doc, "host")
+        .or_else(|| lookup_str(doc, "host.name"))
+        .or_else(|| lookup_str(doc, "host.hostname"))
+        .unwrap_or_default()
+        .to_string();
+
+    let source = lookup_str(doc, "log.file.path")
+        .unwrap_or(default_source)
+        .to_string();
+
+    let severity = lookup_str(doc, "log.level")
+        .or_else(|| lookup_str(doc, "level"))
+        .and_then(level_to_severity);
+
+    let mut fields = doc.clone();
+    if let Some(obj) = fields.as_object_mut() {
+        obj.remove("@timestamp");
+        obj.remove("message");
+    }
+
+    Ok(IngestEvent {
+        ts,
+        host,
+        source,
+        sourcetype: Some("elasticsearch_bulk".to_string()),
+        severity,
+        message,
+        fields,
+        idempotency_key: None,
+    })
+}
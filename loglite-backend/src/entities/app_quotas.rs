@@ -0,0 +1,21 @@
+use sea_orm::entity::prelude::*;
+
+/// Per-app ingestion limits; a `None` limit is unlimited.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "app_quotas")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub app_id: String,
+    pub events_per_sec: Option<i64>,
+    pub bytes_per_sec: Option<i64>,
+    /// Events per UTC day.
+    pub daily_events: Option<i64>,
+    /// Bytes (message plus serialized fields) per UTC day.
+    pub daily_bytes: Option<i64>,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -13,6 +13,8 @@ pub struct Model {
     pub encoding: String,
     pub include_glob: Option<String>,
     pub exclude_glob: Option<String>,
+    /// Name of the parser for tailed lines; the format is detected without one.
+    pub parser: Option<String>,
     pub enabled: bool,
     pub created_at: DateTimeWithTimeZone,
 }
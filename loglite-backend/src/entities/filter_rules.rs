@@ -0,0 +1,24 @@
+use sea_orm::entity::prelude::*;
+
+/// Volume control rules (drop, sample, rate limit) attached to an app or to one of its sources.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "filter_rules")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub app_id: String,
+    /// `app_sources` row the rule is limited to; `None` applies it to every event of the app.
+    pub source_id: Option<i64>,
+    pub name: String,
+    /// Which events the rule applies to, see `filter::Condition`.
+    pub condition: serde_json::Value,
+    /// What happens to matching events, see `filter::FilterAction`.
+    pub action: serde_json::Value,
+    pub enabled: bool,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
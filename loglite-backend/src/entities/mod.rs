@@ -1,11 +1,23 @@
+pub mod app_quotas;
 pub mod app_sources;
 pub mod apps;
 pub mod events;
+pub mod filter_rules;
+pub mod ingest_keys;
+pub mod parsers;
+pub mod pipelines;
+pub mod redaction_rules;
 pub mod tail_offsets;
 
 pub mod prelude {
+    pub use super::app_quotas::Entity as AppQuota;
     pub use super::app_sources::Entity as AppSource;
     pub use super::apps::Entity as App;
     pub use super::events::Entity as Event;
+    pub use super::filter_rules::Entity as FilterRule;
+    pub use super::ingest_keys::Entity as IngestKey;
+    pub use super::parsers::Entity as Parser;
+    pub use super::pipelines::Entity as Pipeline;
+    pub use super::redaction_rules::Entity as RedactionRule;
     pub use super::tail_offsets::Entity as TailOffset;
 }
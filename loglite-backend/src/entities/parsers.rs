@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+
+/// User-defined grok parsers, referenced by name from sources and ingest calls.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "parsers")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    #[sea_orm(unique)]
+    pub name: String,
+    /// Grok pattern, expanded from the built-in pattern library.
+    pub pattern: String,
+    /// chrono format of the timestamp capture; the built-in formats are tried without one.
+    pub timestamp_format: Option<String>,
+    /// Captures mapped to the timestamp, level and message, as JSON.
+    pub mapping: Json,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,22 @@
+use sea_orm::entity::prelude::*;
+
+/// Ingest-time processing pipelines, attached to an app or to one of its sources.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "pipelines")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub app_id: String,
+    /// `app_sources` row the pipeline is limited to; `None` applies it to every event of the app.
+    pub source_id: Option<i64>,
+    pub name: String,
+    /// Ordered processor list, see `pipeline::Processor`.
+    pub processors: serde_json::Value,
+    pub enabled: bool,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
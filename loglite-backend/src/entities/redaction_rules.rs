@@ -0,0 +1,24 @@
+use sea_orm::entity::prelude::*;
+
+/// Per-app PII redaction rules applied to events before they are stored.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "redaction_rules")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub app_id: String,
+    pub name: String,
+    /// Built-in detector (`email`, `credit_card`, `ip`, `bearer_token`) or `regex`.
+    pub detector: String,
+    /// Pattern for the `regex` detector.
+    pub pattern: Option<String>,
+    /// `mask`, `hash` or `drop`.
+    pub action: String,
+    pub enabled: bool,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
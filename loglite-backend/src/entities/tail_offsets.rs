@@ -9,6 +9,8 @@ pub struct Model {
     pub source_id: i64,
     pub file_path: String,
     pub offset_bytes: i64,
+    /// Bumped whenever the file is found truncated and re-read from the start.
+    pub generation: i64,
     pub updated_at: DateTimeWithTimeZone,
 }
 
@@ -0,0 +1,277 @@
+use anyhow::Result;
+use regex::Regex;
+use sea_orm::{DatabaseConnection, EntityTrait, QueryOrder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::entities::prelude::*;
+use crate::models::{FilterRuleStats, FilterStats, IngestEvent};
+use crate::pipeline::get_path;
+
+/// Which events a rule applies to; every criterion given must match, and an empty condition
+/// matches everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Condition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severities: Option<Vec<i32>>,
+    /// Regex matched against `message`, or against the `fields` key `field` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+/// What a rate limit counts events by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateKey {
+    App,
+    #[default]
+    Source,
+    Host,
+}
+
+/// What happens to matching events, stored as JSON tagged by `type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterAction {
+    /// Drop every matching event.
+    Drop,
+    /// Keep every `keep_one_in`-th matching event.
+    Sample { keep_one_in: u64 },
+    /// Keep up to `events_per_sec` matching events per second for each `per` value, then keep
+    /// one in `keep_one_in` of the rest, or none when it is absent.
+    RateLimit {
+        events_per_sec: u64,
+        #[serde(default)]
+        per: RateKey,
+        #[serde(default)]
+        keep_one_in: Option<u64>,
+    },
+}
+
+/// Per-key counts for the current second of a rate limit.
+#[derive(Default)]
+struct RateWindow {
+    second: i64,
+    counts: HashMap<String, u64>,
+}
+
+/// A validated, ready-to-run filter rule.
+pub struct CompiledFilter {
+    condition: Condition,
+    pattern: Option<Regex>,
+    action: FilterAction,
+    matched: AtomicU64,
+    window: parking_lot::Mutex<RateWindow>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl CompiledFilter {
+    /// Validate a rule, compiling its pattern.
+    pub fn compile(condition: Condition, action: FilterAction) -> Result<Self, String> {
+        let pattern = condition
+            .pattern
+            .as_deref()
+            .map(|p| Regex::new(p).map_err(|e| format!("invalid pattern {:?}: {}", p, e)))
+            .transpose()?;
+        match action {
+            FilterAction::Sample { keep_one_in: 0 }
+            | FilterAction::RateLimit {
+                keep_one_in: Some(0),
+                ..
+            } => return Err("keep_one_in must be at least 1".to_string()),
+            _ => {}
+        }
+        Ok(Self {
+            condition,
+            pattern,
+            action,
+            matched: AtomicU64::new(0),
+            window: parking_lot::Mutex::new(RateWindow::default()),
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Compile a stored rule.
+    pub fn from_json(
+        condition: &serde_json::Value,
+        action: &serde_json::Value,
+    ) -> Result<Self, String> {
+        let condition: Condition =
+            serde_json::from_value(condition.clone()).map_err(|e| e.to_string())?;
+        let action: FilterAction =
+            serde_json::from_value(action.clone()).map_err(|e| e.to_string())?;
+        Self::compile(condition, action)
+    }
+
+    fn matches(&self, event: &IngestEvent) -> bool {
+        if let Some(severities) = &self.condition.severities {
+            if !event.severity.is_some_and(|s| severities.contains(&s)) {
+                return false;
+            }
+        }
+        if let Some(re) = &self.pattern {
+            let matched = match &self.condition.field {
+                Some(key) => match get_path(&event.fields, key) {
+                    Some(serde_json::Value::String(s)) => re.is_match(s),
+                    Some(other) => re.is_match(&other.to_string()),
+                    None => false,
+                },
+                None => re.is_match(&event.message),
+            };
+            if !matched {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether a matching event survives the rule's action.
+    fn keep(&self, event: &IngestEvent, now: i64) -> bool {
+        match &self.action {
+            FilterAction::Drop => false,
+            FilterAction::Sample { keep_one_in } => self
+                .matched
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(*keep_one_in),
+            FilterAction::RateLimit {
+                events_per_sec,
+                per,
+                keep_one_in,
+            } => {
+                let key = match per {
+                    RateKey::App => "",
+                    RateKey::Source => event.source.as_str(),
+                    RateKey::Host => event.host.as_str(),
+                };
+                let mut window = self.window.lock();
+                if window.second != now {
+                    window.second = now;
+                    window.counts.clear();
+                }
+                let count = window.counts.entry(key.to_string()).or_insert(0);
+                *count += 1;
+                if *count <= *events_per_sec {
+                    return true;
+                }
+                keep_one_in.is_some_and(|n| (*count - events_per_sec - 1).is_multiple_of(n))
+            }
+        }
+    }
+}
+
+/// Run `filters` over `events` in order, returning which events to keep when any is dropped.
+///
+/// An event dropped by one rule is not seen by later rules and counts towards that rule only.
+pub fn filter_events(filters: &[Arc<CompiledFilter>], events: &[IngestEvent]) -> Option<Vec<bool>> {
+    if filters.is_empty() {
+        return None;
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let mut dropped = vec![0u64; filters.len()];
+    let keep: Vec<bool> = events
+        .iter()
+        .map(|event| {
+            for (filter, dropped) in filters.iter().zip(dropped.iter_mut()) {
+                if filter.matches(event) && !filter.keep(event, now) {
+                    *dropped += 1;
+                    return false;
+                }
+            }
+            true
+        })
+        .collect();
+
+    for (filter, dropped) in filters.iter().zip(&dropped) {
+        if *dropped > 0 {
+            filter.dropped.fetch_add(*dropped, Ordering::Relaxed);
+        }
+    }
+    keep.contains(&false).then_some(keep)
+}
+
+/// Rules of one app, each with the source it is limited to.
+type AppFilters = Vec<(Option<i64>, Arc<CompiledFilter>)>;
+
+/// Enabled filter rules per app, cached in memory and reloaded whenever they change.
+///
+/// Drop counters survive reloads, so a rule keeps its totals when it is edited.
+#[derive(Default)]
+pub struct FilterRegistry {
+    by_app: parking_lot::RwLock<HashMap<String, AppFilters>>,
+    rule_dropped: parking_lot::Mutex<HashMap<i64, Arc<AtomicU64>>>,
+}
+
+impl FilterRegistry {
+    /// Reload rules from the database, activating the enabled ones. Rules that no longer
+    /// compile are skipped with an error log.
+    pub async fn reload(&self, db: &DatabaseConnection) -> Result<()> {
+        let rows = FilterRule::find()
+            .order_by_asc(crate::entities::filter_rules::Column::Id)
+            .all(db)
+            .await?;
+
+        let mut by_app: HashMap<String, AppFilters> = HashMap::new();
+        let mut rule_dropped = self.rule_dropped.lock();
+        let mut live_dropped = HashMap::new();
+        for row in rows {
+            let dropped = rule_dropped.remove(&row.id).unwrap_or_default();
+            live_dropped.insert(row.id, dropped.clone());
+            if !row.enabled {
+                continue;
+            }
+
+            match CompiledFilter::from_json(&row.condition, &row.action) {
+                Ok(filter) => by_app.entry(row.app_id).or_default().push((
+                    row.source_id,
+                    Arc::new(CompiledFilter { dropped, ..filter }),
+                )),
+                Err(e) => tracing::error!("Skipping invalid filter rule {}: {}", row.id, e),
+            }
+        }
+        *rule_dropped = live_dropped;
+
+        *self.by_app.write() = by_app;
+        Ok(())
+    }
+
+    /// Rules for events of `app_id` from `source_id`: app-wide rules first, then those
+    /// attached to the source, each in creation order.
+    pub fn for_source(&self, app_id: &str, source_id: Option<i64>) -> Vec<Arc<CompiledFilter>> {
+        let by_app = self.by_app.read();
+        let Some(filters) = by_app.get(app_id) else {
+            return Vec::new();
+        };
+
+        let app_wide = filters.iter().filter(|(source, _)| source.is_none());
+        let for_source = filters
+            .iter()
+            .filter(|(source, _)| source.is_some() && *source == source_id);
+        app_wide.chain(for_source).map(|(_, f)| f.clone()).collect()
+    }
+
+    /// Drop counts since startup for `rules`.
+    pub fn stats(&self, rules: Vec<crate::entities::filter_rules::Model>) -> FilterStats {
+        let rule_dropped = self.rule_dropped.lock();
+        let rules: Vec<FilterRuleStats> = rules
+            .into_iter()
+            .map(|r| FilterRuleStats {
+                dropped: rule_dropped
+                    .get(&r.id)
+                    .map_or(0, |d| d.load(Ordering::Relaxed)),
+                id: r.id,
+                app_id: r.app_id,
+                source_id: r.source_id,
+                name: r.name,
+            })
+            .collect();
+        FilterStats {
+            dropped_events: rules.iter().map(|r| r.dropped).sum(),
+            rules,
+        }
+    }
+}
@@ -0,0 +1,486 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use flate2::read::MultiGzDecoder;
+use rmpv::Value;
+use rocket::http::Status;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use std::io::{Cursor, Read};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::db::DEFAULT_APP;
+use crate::handlers::ingest_events_for_app;
+use crate::listener::parse_port_list;
+use crate::models::IngestEvent;
+use crate::state::AppState;
+use crate::utils::level_to_severity;
+
+/// Largest buffered, not yet decodable Forward message.
+const MAX_MESSAGE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Forward listener configuration, read from `LOGLITE_FORWARD_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct ForwardConfig {
+    pub bind: String,
+    pub ports: Vec<u16>,
+    /// Fixed app for every event; when unset the first tag segment names the app.
+    pub app_id: Option<String>,
+}
+
+impl ForwardConfig {
+    /// Load the listener configuration from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            bind: std::env::var("LOGLITE_FORWARD_BIND").unwrap_or_else(|_| "0.0.0.0".to_string()),
+            ports: parse_port_list(&std::env::var("LOGLITE_FORWARD_PORTS").unwrap_or_default()),
+            app_id: std::env::var("LOGLITE_FORWARD_APP_ID")
+                .ok()
+                .filter(|s| !s.trim().is_empty()),
+        }
+    }
+}
+
+/// A decoded Forward protocol message: one tag with its entries and options.
+#[derive(Debug, Clone)]
+pub struct ForwardMessage {
+    pub tag: String,
+    pub entries: Vec<(DateTimeWithTimeZone, Value)>,
+    /// `chunk` option; when present the sender expects an `ack` response.
+    pub chunk: Option<String>,
+}
+
+fn default_ts() -> DateTimeWithTimeZone {
+    Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+/// Decode an entry time: integer seconds, float seconds or the `EventTime` ext type.
+fn decode_time(v: &Value) -> DateTimeWithTimeZone {
+    let utc = match v {
+        Value::Integer(i) => i.as_i64().and_then(|s| DateTime::from_timestamp(s, 0)),
+        Value::F64(f) => DateTime::from_timestamp(f.trunc() as i64, (f.fract() * 1e9) as u32),
+        Value::Ext(0, data) if data.len() == 8 => {
+            let secs = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            let nanos = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+            DateTime::from_timestamp(secs as i64, nanos)
+        }
+        _ => None,
+    };
+    utc.map(|t| t.with_timezone(&FixedOffset::east_opt(0).unwrap()))
+        .unwrap_or_else(default_ts)
+}
+
+fn decode_entry(v: &Value) -> Result<(DateTimeWithTimeZone, Value)> {
+    match v.as_array().map(|a| a.as_slice()) {
+        Some([time, record, ..]) => Ok((decode_time(time), record.clone())),
+        _ => bail!("entry must be [time, record]"),
+    }
+}
+
+fn map_get<'a>(map: &'a Value, key: &str) -> Option<&'a Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+}
+
+/// Decode the entries of a (Compressed)PackedForward message.
+fn decode_packed_entries(
+    packed: &[u8],
+    option: Option<&Value>,
+) -> Result<Vec<(DateTimeWithTimeZone, Value)>> {
+    let compressed = option
+        .and_then(|o| map_get(o, "compressed"))
+        .and_then(|c| c.as_str());
+    let raw = match compressed {
+        Some("gzip") => {
+            let mut out = Vec::new();
+            MultiGzDecoder::new(packed)
+                .take(MAX_MESSAGE_BYTES as u64 + 1)
+                .read_to_end(&mut out)?;
+            if out.len() > MAX_MESSAGE_BYTES {
+                bail!("decompressed entries exceed {} bytes", MAX_MESSAGE_BYTES);
+            }
+            out
+        }
+        Some(other) => bail!("unsupported compression {}", other),
+        None => packed.to_vec(),
+    };
+
+    let mut cursor = Cursor::new(raw.as_slice());
+    let mut entries = Vec::new();
+    while (cursor.position() as usize) < raw.len() {
+        entries.push(decode_entry(&rmpv::decode::read_value(&mut cursor)?)?);
+    }
+    Ok(entries)
+}
+
+/// Option map of a Forward message: the element after the entries, or after the record in
+/// Message mode.
+fn message_option(parts: &[Value]) -> Option<&Value> {
+    match parts.get(1)? {
+        Value::Array(_) | Value::Binary(_) | Value::String(_) => parts.get(2),
+        _ => parts.get(3),
+    }
+}
+
+/// `chunk` option of a Forward message, read without decoding its entries so that even an
+/// invalid message can be acknowledged.
+fn message_chunk(value: &Value) -> Option<String> {
+    let option = message_option(value.as_array()?)?;
+    map_get(option, "chunk")
+        .and_then(|c| c.as_str())
+        .map(|c| c.to_string())
+}
+
+/// Decode one top-level Forward message in any of the four modes.
+///
+/// - Message: `[tag, time, record, option?]`
+/// - Forward: `[tag, [[time, record], ...], option?]`
+/// - PackedForward: `[tag, <msgpack stream of entries>, option?]`
+/// - CompressedPackedForward: PackedForward with `option.compressed == "gzip"`
+pub fn decode_forward_message(value: &Value) -> Result<ForwardMessage> {
+    let parts = value
+        .as_array()
+        .ok_or_else(|| anyhow!("forward message must be an array"))?;
+    let tag = parts
+        .first()
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| anyhow!("forward message is missing its tag"))?
+        .to_string();
+    let second = parts
+        .get(1)
+        .ok_or_else(|| anyhow!("forward message has no entries"))?;
+    let option = message_option(parts);
+
+    let entries = match second {
+        Value::Array(entries) => entries
+            .iter()
+            .map(decode_entry)
+            .collect::<Result<Vec<_>>>()?,
+        Value::Binary(packed) => decode_packed_entries(packed, option)?,
+        Value::String(packed) => decode_packed_entries(packed.as_bytes(), option)?,
+        time => {
+            let record = parts
+                .get(2)
+                .ok_or_else(|| anyhow!("message mode is missing its record"))?;
+            vec![(decode_time(time), record.clone())]
+        }
+    };
+
+    Ok(ForwardMessage {
+        tag,
+        entries,
+        chunk: message_chunk(value),
+    })
+}
+
+/// Convert a MessagePack value to JSON. Binary data is decoded as lossy UTF-8.
+fn msgpack_to_json(v: &Value) -> serde_json::Value {
+    match v {
+        Value::Nil => serde_json::Value::Null,
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::Integer(i) => i
+            .as_i64()
+            .map(serde_json::Value::from)
+            .or_else(|| i.as_u64().map(serde_json::Value::from))
+            .unwrap_or(serde_json::Value::Null),
+        Value::F32(f) => serde_json::Value::from(*f as f64),
+        Value::F64(f) => serde_json::Value::from(*f),
+        Value::String(s) => serde_json::Value::String(String::from_utf8_lossy(s.as_bytes()).into()),
+        Value::Binary(b) => serde_json::Value::String(String::from_utf8_lossy(b).into()),
+        Value::Array(a) => serde_json::Value::Array(a.iter().map(msgpack_to_json).collect()),
+        Value::Map(m) => serde_json::Value::Object(
+            m.iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        Value::String(s) => String::from_utf8_lossy(s.as_bytes()).into(),
+                        other => other.to_string(),
+                    };
+                    (key, msgpack_to_json(v))
+                })
+                .collect(),
+        ),
+        Value::Ext(_, data) => serde_json::Value::String(hex::encode(data)),
+    }
+}
+
+/// Split a tag into `(app, source)`: `web.nginx.access` routes to app `web`.
+///
+/// A tag without a dot has no app part and is used as the source only.
+pub fn split_tag(tag: &str) -> (Option<&str>, &str) {
+    match tag.split_once('.') {
+        Some((app, rest)) if !app.is_empty() && !rest.is_empty() => (Some(app), rest),
+        _ => (None, tag),
+    }
+}
+
+/// Convert a record into an ingest event.
+///
+/// The `log`, `message` or `msg` key becomes the message; the remaining keys go to `fields`.
+pub fn record_to_event(ts: DateTimeWithTimeZone, source: &str, record: &Value) -> IngestEvent {
+    let mut fields = match msgpack_to_json(record) {
+        serde_json::Value::Object(map) => map,
+        other => {
+            let mut map = serde_json::Map::new();
+            map.insert("record".to_string(), other);
+            map
+        }
+    };
+
+    let message = ["log", "message", "msg"]
+        .iter()
+        .find_map(|k| match fields.get(*k) {
+            Some(serde_json::Value::String(_)) => fields.remove(*k),
+            _ => None,
+        })
+        .and_then(|v| v.as_str().map(|s| s.trim_end_matches('\n').to_string()))
+        .unwrap_or_else(|| serde_json::Value::Object(fields.clone()).to_string());
+
+    let host = ["host", "hostname"]
+        .iter()
+        .find_map(|k| fields.get(*k).and_then(|v| v.as_str()))
+        .unwrap_or_default()
+        .to_string();
+    let severity = ["level", "severity"]
+        .iter()
+        .find_map(|k| fields.get(*k).and_then(|v| v.as_str()))
+        .and_then(level_to_severity);
+
+    IngestEvent {
+        ts,
+        host,
+        source: source.to_string(),
+        sourcetype: Some("fluentd".to_string()),
+        severity,
+        message,
+        fields: serde_json::Value::Object(fields),
+        idempotency_key: None,
+    }
+}
+
+/// Background task running the Fluentd Forward protocol (v1) TCP listener.
+///
+/// Only started when `LOGLITE_FORWARD_PORTS` is set. Each message is persisted before the
+/// `ack` for its `chunk` is written back, so `require_ack_response` gives at-least-once
+/// delivery. Shared-key handshakes are not supported.
+pub async fn forward_listener_loop(state: Arc<AppState>) {
+    let config = Arc::new(ForwardConfig::from_env());
+
+    for &port in &config.ports {
+        let addr = format!("{}:{}", config.bind, port);
+        let listener = match TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("Failed to bind forward listener {}: {}", addr, e);
+                continue;
+            }
+        };
+        tracing::info!("Fluentd forward listener on {}", addr);
+
+        let state = state.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let state = state.clone();
+                        let config = config.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = handle_connection(stream, peer, &state, &config).await {
+                                tracing::debug!("Forward connection {} closed: {}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) => tracing::error!("Forward accept failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// Finds where a MessagePack value ends without decoding it.
+///
+/// The scan resumes where it stopped when more bytes arrive, so a large message is walked
+/// once however many reads it spans, instead of being re-parsed from its first byte.
+#[derive(Default)]
+struct ValueScanner {
+    /// Bytes of the current value scanned so far.
+    pos: usize,
+    /// Items still expected by each open array or map, innermost last.
+    open: Vec<u64>,
+}
+
+impl ValueScanner {
+    /// Length of the value at the start of `buf`, or `None` while it is incomplete.
+    fn scan(&mut self, buf: &[u8]) -> Result<Option<usize>> {
+        loop {
+            let Some((header, payload, items)) = item_header(&buf[self.pos..])? else {
+                return Ok(None);
+            };
+            let end = self.pos as u64 + header as u64 + payload;
+            if end > buf.len() as u64 {
+                return Ok(None);
+            }
+            self.pos = end as usize;
+
+            if items > 0 {
+                self.open.push(items);
+                continue;
+            }
+            // The item is complete; close every container it completes.
+            loop {
+                match self.open.last_mut() {
+                    None => {
+                        let len = self.pos;
+                        *self = Self::default();
+                        return Ok(Some(len));
+                    }
+                    Some(left) => {
+                        *left -= 1;
+                        if *left > 0 {
+                            break;
+                        }
+                        self.open.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Header length, payload length and number of nested items of the MessagePack item at the
+/// start of `b`, or `None` when its header has not fully arrived.
+fn item_header(b: &[u8]) -> Result<Option<(usize, u64, u64)>> {
+    let Some(&marker) = b.first() else {
+        return Ok(None);
+    };
+    // Big-endian length of `n` bytes following the marker.
+    let len = |n: usize| -> Option<u64> {
+        let bytes = b.get(1..1 + n)?;
+        Some(bytes.iter().fold(0u64, |acc, &x| (acc << 8) | x as u64))
+    };
+    let header = match marker {
+        0x00..=0x7f | 0xc0 | 0xc2 | 0xc3 | 0xe0..=0xff => Some((1, 0, 0)),
+        0x80..=0x8f => Some((1, 0, 2 * (marker & 0x0f) as u64)),
+        0x90..=0x9f => Some((1, 0, (marker & 0x0f) as u64)),
+        0xa0..=0xbf => Some((1, (marker & 0x1f) as u64, 0)),
+        0xc1 => bail!("invalid MessagePack marker 0xc1"),
+        0xc4 | 0xd9 => len(1).map(|n| (2, n, 0)),
+        0xc5 | 0xda => len(2).map(|n| (3, n, 0)),
+        0xc6 | 0xdb => len(4).map(|n| (5, n, 0)),
+        // ext: length, then a type byte.
+        0xc7 => len(1).map(|n| (3, n, 0)),
+        0xc8 => len(2).map(|n| (4, n, 0)),
+        0xc9 => len(4).map(|n| (6, n, 0)),
+        0xca => Some((1, 4, 0)),
+        0xcb => Some((1, 8, 0)),
+        0xcc | 0xd0 => Some((1, 1, 0)),
+        0xcd | 0xd1 => Some((1, 2, 0)),
+        0xce | 0xd2 => Some((1, 4, 0)),
+        0xcf | 0xd3 => Some((1, 8, 0)),
+        // fixext: a type byte and 1 to 16 data bytes.
+        0xd4 => Some((2, 1, 0)),
+        0xd5 => Some((2, 2, 0)),
+        0xd6 => Some((2, 4, 0)),
+        0xd7 => Some((2, 8, 0)),
+        0xd8 => Some((2, 16, 0)),
+        0xdc => len(2).map(|n| (3, 0, n)),
+        0xdd => len(4).map(|n| (5, 0, n)),
+        0xde => len(2).map(|n| (3, 0, 2 * n)),
+        0xdf => len(4).map(|n| (5, 0, 2 * n)),
+    };
+    Ok(header)
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    state: &AppState,
+    config: &ForwardConfig,
+) -> Result<()> {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut read_buf = vec![0u8; 64 * 1024];
+    let mut scanner = ValueScanner::default();
+
+    loop {
+        // Decode as many complete messages as the buffer holds.
+        let mut start = 0;
+        while let Some(len) = scanner.scan(&buf[start..])? {
+            let value = rmpv::decode::read_value(&mut &buf[start..start + len])?;
+            start += len;
+
+            // An invalid message would be invalid again when resent, so its chunk is still
+            // acknowledged.
+            let message = match decode_forward_message(&value) {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::warn!("Dropping invalid forward message from {}: {}", peer, e);
+                    if let Some(chunk) = message_chunk(&value) {
+                        write_ack(&mut stream, chunk).await?;
+                    }
+                    continue;
+                }
+            };
+
+            let (tag_app, tag_source) = split_tag(&message.tag);
+            let (app_id, source) = match (&config.app_id, tag_app) {
+                (Some(app_id), _) => (app_id.clone(), message.tag.as_str()),
+                (None, Some(app)) => (app.to_string(), tag_source),
+                (None, None) => (DEFAULT_APP.to_string(), tag_source),
+            };
+
+            // Keyed on the chunk id, so a chunk resent after a lost ack is stored once.
+            let events: Vec<IngestEvent> = message
+                .entries
+                .iter()
+                .enumerate()
+                .map(|(i, (ts, record))| {
+                    let mut event = record_to_event(*ts, source, record);
+                    event.idempotency_key = message
+                        .chunk
+                        .as_ref()
+                        .map(|c| format!("forward:{}:{}", c, i));
+                    event
+                })
+                .collect();
+
+            // Without an ack the sender would resend; drop the connection so it does. A
+            // rejected app would be rejected again, so those events are dropped instead.
+            match ingest_events_for_app(state, &app_id, &events).await {
+                Ok(_) => {}
+                Err(e) if e.status == Status::NotFound => tracing::warn!(
+                    "Dropping {} forward events for unknown app {}",
+                    events.len(),
+                    app_id
+                ),
+                Err(e) => return Err(anyhow!("failed to ingest forward events: {}", e)),
+            }
+
+            if let Some(chunk) = message.chunk {
+                write_ack(&mut stream, chunk).await?;
+            }
+        }
+        buf.drain(..start);
+
+        if buf.len() > MAX_MESSAGE_BYTES {
+            bail!("forward message exceeds {} bytes", MAX_MESSAGE_BYTES);
+        }
+
+        let n = stream.read(&mut read_buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&read_buf[..n]);
+    }
+}
+
+/// Write the `ack` response for `chunk`.
+async fn write_ack(stream: &mut TcpStream, chunk: String) -> Result<()> {
+    let ack = Value::Map(vec![(Value::from("ack"), Value::from(chunk))]);
+    let mut out = Vec::new();
+    rmpv::encode::write_value(&mut out, &ack)?;
+    stream.write_all(&out).await?;
+    Ok(())
+}
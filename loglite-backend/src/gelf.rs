@@ -0,0 +1,385 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+
+use crate::db::DEFAULT_APP;
+use crate::listener::{flush_loop, parse_port_list, RoutedEvent};
+use crate::models::IngestEvent;
+use crate::state::AppState;
+
+/// Magic bytes prefixing every chunked GELF UDP datagram.
+const CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+
+/// GELF allows at most 128 chunks per message.
+const MAX_CHUNKS: usize = 128;
+
+/// Incomplete chunked messages are discarded after this long (per the GELF spec).
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bound on concurrently reassembled messages, to cap memory use.
+const MAX_PENDING_MESSAGES: usize = 1024;
+
+/// Largest GELF message accepted after decompression.
+pub const MAX_MESSAGE_BYTES: usize = 8 * 1024 * 1024;
+
+/// GELF listener configuration, read from `LOGLITE_GELF_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct GelfConfig {
+    pub bind: String,
+    pub udp_ports: Vec<u16>,
+    pub tcp_ports: Vec<u16>,
+    pub app_id: String,
+    pub flush_interval: Duration,
+}
+
+impl GelfConfig {
+    /// Load the listener configuration from the environment.
+    pub fn from_env() -> Self {
+        let bind = std::env::var("LOGLITE_GELF_BIND").unwrap_or_else(|_| "0.0.0.0".to_string());
+        let udp_ports =
+            parse_port_list(&std::env::var("LOGLITE_GELF_UDP_PORTS").unwrap_or_default());
+        let tcp_ports =
+            parse_port_list(&std::env::var("LOGLITE_GELF_TCP_PORTS").unwrap_or_default());
+        let flush_interval_ms: u64 = std::env::var("LOGLITE_GELF_FLUSH_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(1000);
+
+        Self {
+            bind,
+            udp_ports,
+            tcp_ports,
+            app_id: default_app_id(),
+            flush_interval: Duration::from_millis(flush_interval_ms),
+        }
+    }
+}
+
+/// App that GELF events are ingested into when no app is given explicitly.
+pub fn default_app_id() -> String {
+    std::env::var("LOGLITE_GELF_APP_ID").unwrap_or_else(|_| DEFAULT_APP.to_string())
+}
+
+fn default_ts() -> DateTimeWithTimeZone {
+    Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+/// Decompress a GELF payload if it is gzip or zlib encoded.
+///
+/// The encoding is detected from the magic bytes, as GELF clients do not announce it.
+/// Output is capped at [`MAX_MESSAGE_BYTES`] to guard against decompression bombs.
+pub fn decompress_payload(payload: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let limit = MAX_MESSAGE_BYTES as u64 + 1;
+    match payload {
+        [0x1f, 0x8b, ..] => {
+            GzDecoder::new(payload).take(limit).read_to_end(&mut out)?;
+        }
+        [0x78, b, ..] if (0x7800u16 | *b as u16).is_multiple_of(31) => {
+            ZlibDecoder::new(payload)
+                .take(limit)
+                .read_to_end(&mut out)?;
+        }
+        _ => out.extend_from_slice(payload),
+    }
+    if out.len() > MAX_MESSAGE_BYTES {
+        bail!("GELF message exceeds {} bytes", MAX_MESSAGE_BYTES);
+    }
+    Ok(out)
+}
+
+/// Parse a (decompressed) GELF JSON document into an ingest event.
+///
+/// `full_message` becomes the event message when present, so the long form is what gets
+/// indexed; `short_message` is then kept in `fields`. Additional `_`-prefixed fields are
+/// stored in `fields` without the underscore.
+pub fn parse_gelf_message(payload: &[u8], peer_host: &str) -> Result<IngestEvent> {
+    let json: serde_json::Value = serde_json::from_slice(payload)?;
+    let obj = json
+        .as_object()
+        .ok_or_else(|| anyhow!("GELF payload is not a JSON object"))?;
+
+    let short_message = obj
+        .get("short_message")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("GELF payload is missing short_message"))?;
+    let full_message = obj
+        .get("full_message")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty());
+
+    let mut fields = serde_json::Map::new();
+    for (key, value) in obj {
+        // `_id` is reserved by the spec and must not be stored as a field.
+        if let Some(name) = key.strip_prefix('_') {
+            if !name.is_empty() && name != "id" {
+                fields.insert(name.to_string(), value.clone());
+            }
+        } else if matches!(key.as_str(), "facility" | "file" | "line" | "version") {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+
+    let message = match full_message {
+        Some(full) => {
+            fields.insert(
+                "short_message".to_string(),
+                serde_json::Value::String(short_message.to_string()),
+            );
+            full.to_string()
+        }
+        None => short_message.to_string(),
+    };
+
+    let ts = obj
+        .get("timestamp")
+        .and_then(|v| v.as_f64())
+        .and_then(|secs| {
+            let whole = secs.floor();
+            let nanos = ((secs - whole) * 1_000_000_000.0) as u32;
+            DateTime::from_timestamp(whole as i64, nanos)
+        })
+        .map(|utc| utc.with_timezone(&FixedOffset::east_opt(0).unwrap()))
+        .unwrap_or_else(default_ts);
+
+    let severity = obj
+        .get("level")
+        .and_then(|v| v.as_i64())
+        .filter(|l| (0..=7).contains(l))
+        .map(|l| l as i32);
+
+    let host = obj
+        .get("host")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .unwrap_or(peer_host)
+        .to_string();
+
+    // Docker's gelf driver tags every message with the container name.
+    let source = ["_container_name", "_tag", "facility"]
+        .iter()
+        .find_map(|k| obj.get(*k).and_then(|v| v.as_str()))
+        .unwrap_or("gelf")
+        .to_string();
+
+    Ok(IngestEvent {
+        ts,
+        host,
+        source,
+        sourcetype: Some("gelf".to_string()),
+        severity,
+        message,
+        fields: serde_json::Value::Object(fields),
+        idempotency_key: None,
+    })
+}
+
+/// Decompress and parse a complete GELF payload.
+pub fn decode_gelf_payload(payload: &[u8], peer_host: &str) -> Result<IngestEvent> {
+    let decompressed = decompress_payload(payload)?;
+    parse_gelf_message(&decompressed, peer_host)
+}
+
+/// Partially received chunked message.
+struct ChunkedMessage {
+    first_seen: Instant,
+    received: usize,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+/// Reassembles chunked GELF UDP datagrams.
+#[derive(Default)]
+pub struct ChunkAssembler {
+    pending: HashMap<[u8; 8], ChunkedMessage>,
+}
+
+impl ChunkAssembler {
+    /// Feed one datagram. Returns the full payload once every chunk has arrived, or the
+    /// datagram itself when it is not chunked.
+    pub fn push(&mut self, datagram: &[u8]) -> Option<Vec<u8>> {
+        if !datagram.starts_with(&CHUNK_MAGIC) {
+            return Some(datagram.to_vec());
+        }
+        if datagram.len() < 12 {
+            return None;
+        }
+
+        self.evict_expired();
+
+        let mut message_id = [0u8; 8];
+        message_id.copy_from_slice(&datagram[2..10]);
+        let seq = datagram[10] as usize;
+        let count = datagram[11] as usize;
+        if count == 0 || count > MAX_CHUNKS || seq >= count {
+            return None;
+        }
+
+        if !self.pending.contains_key(&message_id) && self.pending.len() >= MAX_PENDING_MESSAGES {
+            tracing::warn!("Too many incomplete GELF chunked messages, dropping chunk");
+            return None;
+        }
+
+        let entry = self
+            .pending
+            .entry(message_id)
+            .or_insert_with(|| ChunkedMessage {
+                first_seen: Instant::now(),
+                received: 0,
+                chunks: vec![None; count],
+            });
+        if entry.chunks.len() != count {
+            return None;
+        }
+        if entry.chunks[seq].is_none() {
+            entry.chunks[seq] = Some(datagram[12..].to_vec());
+            entry.received += 1;
+        }
+        if entry.received < count {
+            return None;
+        }
+
+        let complete = self.pending.remove(&message_id)?;
+        Some(complete.chunks.into_iter().flatten().flatten().collect())
+    }
+
+    fn evict_expired(&mut self) {
+        self.pending
+            .retain(|_, m| m.first_seen.elapsed() < CHUNK_TIMEOUT);
+    }
+}
+
+/// Background task running the GELF UDP and TCP listeners.
+///
+/// Listeners are only started for ports listed in `LOGLITE_GELF_UDP_PORTS` /
+/// `LOGLITE_GELF_TCP_PORTS`. Events go to `LOGLITE_GELF_APP_ID`.
+pub async fn gelf_listener_loop(state: Arc<AppState>) {
+    let config = GelfConfig::from_env();
+    if config.udp_ports.is_empty() && config.tcp_ports.is_empty() {
+        return;
+    }
+
+    let (tx, rx) = mpsc::channel::<RoutedEvent>(10_000);
+
+    for &port in &config.udp_ports {
+        let addr = format!("{}:{}", config.bind, port);
+        match UdpSocket::bind(&addr).await {
+            Ok(socket) => {
+                tracing::info!("GELF UDP listener on {}", addr);
+                tokio::spawn(run_udp(socket, config.app_id.clone(), tx.clone()));
+            }
+            Err(e) => tracing::error!("Failed to bind GELF UDP {}: {}", addr, e),
+        }
+    }
+
+    for &port in &config.tcp_ports {
+        let addr = format!("{}:{}", config.bind, port);
+        match TcpListener::bind(&addr).await {
+            Ok(listener) => {
+                tracing::info!("GELF TCP listener on {}", addr);
+                tokio::spawn(run_tcp(listener, config.app_id.clone(), tx.clone()));
+            }
+            Err(e) => tracing::error!("Failed to bind GELF TCP {}: {}", addr, e),
+        }
+    }
+
+    drop(tx);
+    flush_loop(state, rx, config.flush_interval, "gelf").await;
+}
+
+async fn run_udp(socket: UdpSocket, app_id: String, tx: mpsc::Sender<RoutedEvent>) {
+    let mut buf = vec![0u8; 65_536];
+    let mut assembler = ChunkAssembler::default();
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::error!("GELF UDP receive failed: {}", e);
+                continue;
+            }
+        };
+        let Some(payload) = assembler.push(&buf[..len]) else {
+            continue;
+        };
+        if let Some(event) = to_event(&payload, &peer) {
+            if tx.send((app_id.clone(), event)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn run_tcp(listener: TcpListener, app_id: String, tx: mpsc::Sender<RoutedEvent>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let app_id = app_id.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_tcp_connection(stream, peer, app_id, tx).await {
+                        tracing::debug!("GELF TCP connection {} closed: {}", peer, e);
+                    }
+                });
+            }
+            Err(e) => tracing::error!("GELF TCP accept failed: {}", e),
+        }
+    }
+}
+
+/// Read null-byte delimited GELF frames from a TCP connection.
+async fn handle_tcp_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    app_id: String,
+    tx: mpsc::Sender<RoutedEvent>,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut frame: Vec<u8> = Vec::new();
+
+    loop {
+        frame.clear();
+        let n = (&mut reader)
+            .take(MAX_MESSAGE_BYTES as u64 + 1)
+            .read_until(0, &mut frame)
+            .await?;
+        if n == 0 {
+            return Ok(());
+        }
+        if frame.last() == Some(&0) {
+            frame.pop();
+        } else if frame.len() > MAX_MESSAGE_BYTES {
+            bail!("GELF frame exceeds {} bytes", MAX_MESSAGE_BYTES);
+        }
+        // Some clients terminate frames with a newline as well.
+        while matches!(frame.last(), Some(b'\n' | b'\r')) {
+            frame.pop();
+        }
+        if frame.is_empty() {
+            continue;
+        }
+
+        if let Some(event) = to_event(&frame, &peer) {
+            if tx.send((app_id.clone(), event)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn to_event(payload: &[u8], peer: &SocketAddr) -> Option<IngestEvent> {
+    match decode_gelf_payload(payload, &peer.ip().to_string()) {
+        Ok(event) => Some(event),
+        Err(e) => {
+            tracing::debug!("Dropping invalid GELF message from {}: {}", peer, e);
+            None
+        }
+    }
+}
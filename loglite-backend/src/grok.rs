@@ -0,0 +1,217 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+/// Deepest chain of library patterns referring to each other.
+const MAX_NESTING: usize = 16;
+
+/// Built-in patterns, usable as `%{NAME}` in parser patterns and in each other.
+const PATTERN_LIBRARY: &[(&str, &str)] = &[
+    ("USERNAME", r"[a-zA-Z0-9._-]+"),
+    ("USER", r"%{USERNAME}"),
+    ("INT", r"[+-]?\d+"),
+    ("BASE10NUM", r"[+-]?(?:\d+(?:\.\d*)?|\.\d+)"),
+    ("NUMBER", r"%{BASE10NUM}"),
+    ("BASE16NUM", r"[+-]?(?:0x)?[0-9A-Fa-f]+"),
+    ("POSINT", r"\b[1-9]\d*\b"),
+    ("NONNEGINT", r"\b\d+\b"),
+    ("WORD", r"\b\w+\b"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("QUOTEDSTRING", r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#),
+    ("QS", r"%{QUOTEDSTRING}"),
+    (
+        "UUID",
+        r"[A-Fa-f0-9]{8}-(?:[A-Fa-f0-9]{4}-){3}[A-Fa-f0-9]{12}",
+    ),
+    (
+        "IPV4",
+        r"(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)",
+    ),
+    (
+        "IPV6",
+        r"(?:[0-9A-Fa-f]{1,4}:){7}[0-9A-Fa-f]{1,4}|(?:[0-9A-Fa-f]{1,4}:){1,6}(?::[0-9A-Fa-f]{1,4}){1,6}|(?:[0-9A-Fa-f]{1,4}:){1,7}:|:(?::[0-9A-Fa-f]{1,4}){1,7}|::",
+    ),
+    ("IP", r"%{IPV4}|%{IPV6}"),
+    (
+        "HOSTNAME",
+        r"\b[0-9A-Za-z][0-9A-Za-z-]{0,62}(?:\.[0-9A-Za-z][0-9A-Za-z-]{0,62})*\b",
+    ),
+    ("IPORHOST", r"%{IP}|%{HOSTNAME}"),
+    ("HOSTPORT", r"%{IPORHOST}:%{POSINT}"),
+    ("EMAILADDRESS", r"[a-zA-Z0-9._%+-]+@%{HOSTNAME}"),
+    ("PATH", r"(?:/[\w%!$@:.,+~-]*)+"),
+    ("URIPROTO", r"[A-Za-z][A-Za-z0-9+.-]+"),
+    ("URIPATH", r"(?:/[A-Za-z0-9$.+!*'(){},~:;=@#%&_-]*)+"),
+    ("URIPARAM", r"\?[A-Za-z0-9$.+!*'|(){},~@#%&/=:;_?\[\]<>-]*"),
+    ("URIPATHPARAM", r"%{URIPATH}(?:%{URIPARAM})?"),
+    (
+        "URI",
+        r"%{URIPROTO}://(?:%{USER}(?::[^@]*)?@)?(?:%{IPORHOST}(?::%{POSINT})?)?(?:%{URIPATHPARAM})?",
+    ),
+    (
+        "MONTH",
+        r"\b(?:[Jj]an(?:uary)?|[Ff]eb(?:ruary)?|[Mm]ar(?:ch)?|[Aa]pr(?:il)?|[Mm]ay|[Jj]un(?:e)?|[Jj]ul(?:y)?|[Aa]ug(?:ust)?|[Ss]ep(?:tember)?|[Oo]ct(?:ober)?|[Nn]ov(?:ember)?|[Dd]ec(?:ember)?)\b",
+    ),
+    ("MONTHNUM", r"0?[1-9]|1[0-2]"),
+    ("MONTHDAY", r"0[1-9]|[12][0-9]|3[01]|[1-9]"),
+    (
+        "DAY",
+        r"\b(?:Mon(?:day)?|Tue(?:sday)?|Wed(?:nesday)?|Thu(?:rsday)?|Fri(?:day)?|Sat(?:urday)?|Sun(?:day)?)\b",
+    ),
+    ("YEAR", r"\d\d(?:\d\d)?"),
+    ("HOUR", r"2[0123]|[01]?[0-9]"),
+    ("MINUTE", r"[0-5][0-9]"),
+    ("SECOND", r"(?:[0-5]?[0-9]|60)(?:[:.,][0-9]+)?"),
+    ("TIME", r"\b%{HOUR}:%{MINUTE}(?::%{SECOND})?\b"),
+    ("DATE_US", r"%{MONTHNUM}[/-]%{MONTHDAY}[/-]%{YEAR}"),
+    ("DATE_EU", r"%{MONTHDAY}[./-]%{MONTHNUM}[./-]%{YEAR}"),
+    ("DATE", r"%{DATE_US}|%{DATE_EU}"),
+    ("DATESTAMP", r"%{DATE}[- ]%{TIME}"),
+    ("ISO8601_TIMEZONE", r"Z|[+-]%{HOUR}(?::?%{MINUTE})?"),
+    (
+        "TIMESTAMP_ISO8601",
+        r"%{YEAR}-%{MONTHNUM}-%{MONTHDAY}[T ]%{HOUR}:?%{MINUTE}(?::?%{SECOND})?%{ISO8601_TIMEZONE}?",
+    ),
+    ("HTTPDATE", r"%{MONTHDAY}/%{MONTH}/%{YEAR}:%{TIME} %{INT}"),
+    ("SYSLOGTIMESTAMP", r"%{MONTH} +%{MONTHDAY} %{TIME}"),
+    (
+        "LOGLEVEL",
+        r"[Aa]lert|ALERT|[Tt]race|TRACE|[Dd]ebug|DEBUG|[Nn]otice|NOTICE|[Ii]nfo(?:rmation)?|INFO(?:RMATION)?|[Ww]arn(?:ing)?|WARN(?:ING)?|[Ee]rr(?:or)?|ERR(?:OR)?|[Cc]rit(?:ical)?|CRIT(?:ICAL)?|[Ff]atal|FATAL|[Ss]evere|SEVERE|[Ee]merg(?:ency)?|EMERG(?:ENCY)?",
+    ),
+];
+
+lazy_static! {
+    static ref LIBRARY: HashMap<&'static str, &'static str> =
+        PATTERN_LIBRARY.iter().copied().collect();
+    /// `%{NAME}`, `%{NAME:field}` or `%{NAME:field:type}`.
+    static ref REFERENCE: Regex =
+        Regex::new(r"%\{(\w+)(?::([\w.@\[\]-]+))?(?::(int|float))?\}").unwrap();
+}
+
+/// Names of the built-in patterns.
+pub fn library_names() -> Vec<&'static str> {
+    PATTERN_LIBRARY.iter().map(|(name, _)| *name).collect()
+}
+
+/// How a captured value is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureType {
+    String,
+    Int,
+    Float,
+}
+
+/// A named capture of a compiled pattern.
+#[derive(Debug, Clone)]
+pub struct GrokCapture {
+    /// Group name in the compiled regex.
+    group: String,
+    pub field: String,
+    pub kind: CaptureType,
+}
+
+/// A grok pattern compiled to a regex.
+#[derive(Debug, Clone)]
+pub struct Grok {
+    regex: Regex,
+    captures: Vec<GrokCapture>,
+}
+
+impl Grok {
+    /// Compile `pattern`, expanding `%{NAME}` references from the pattern library.
+    ///
+    /// `%{NAME:field}` captures the match as `field`, and `%{NAME:field:int}` or
+    /// `%{NAME:field:float}` stores it as a number. Plain regex named groups such as
+    /// `(?P<field>...)` are captured as strings.
+    pub fn compile(pattern: &str) -> Result<Self, String> {
+        let mut captures = Vec::new();
+        let expanded = expand(pattern, &mut captures, 0)?;
+        let regex = Regex::new(&expanded).map_err(|e| format!("invalid pattern: {}", e))?;
+        for name in regex.capture_names().flatten() {
+            if !captures.iter().any(|c| c.group == name) {
+                captures.push(GrokCapture {
+                    group: name.to_string(),
+                    field: name.to_string(),
+                    kind: CaptureType::String,
+                });
+            }
+        }
+        Ok(Self { regex, captures })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    /// Captured values of the first match in `text`, by field, skipping empty and unmatched
+    /// groups. A value that does not convert to its type is kept as a string.
+    pub fn parse(&self, text: &str) -> Option<Vec<(&str, serde_json::Value)>> {
+        let caps = self.regex.captures(text)?;
+        let values = self
+            .captures
+            .iter()
+            .filter_map(|c| {
+                let value = caps.name(&c.group)?.as_str();
+                if value.is_empty() {
+                    return None;
+                }
+                let typed = match c.kind {
+                    CaptureType::Int => value.parse::<i64>().ok().map(serde_json::Value::from),
+                    CaptureType::Float => value.parse::<f64>().ok().map(serde_json::Value::from),
+                    CaptureType::String => None,
+                };
+                Some((
+                    c.field.as_str(),
+                    typed.unwrap_or_else(|| serde_json::Value::String(value.to_string())),
+                ))
+            })
+            .collect();
+        Some(values)
+    }
+}
+
+fn expand(pattern: &str, captures: &mut Vec<GrokCapture>, depth: usize) -> Result<String, String> {
+    if depth > MAX_NESTING {
+        return Err("patterns nest too deeply".to_string());
+    }
+
+    let mut error = None;
+    let expanded = REFERENCE.replace_all(pattern, |caps: &Captures| {
+        let name = &caps[1];
+        let Some(definition) = LIBRARY.get(name) else {
+            error.get_or_insert_with(|| format!("unknown pattern %{{{}}}", name));
+            return String::new();
+        };
+        let inner = match expand(definition, captures, depth + 1) {
+            Ok(inner) => inner,
+            Err(e) => {
+                error.get_or_insert(e);
+                return String::new();
+            }
+        };
+        match caps.get(2) {
+            Some(field) => {
+                let group = format!("__grok{}", captures.len());
+                captures.push(GrokCapture {
+                    group: group.clone(),
+                    field: field.as_str().to_string(),
+                    kind: match caps.get(3).map(|t| t.as_str()) {
+                        Some("int") => CaptureType::Int,
+                        Some("float") => CaptureType::Float,
+                        _ => CaptureType::String,
+                    },
+                });
+                format!("(?P<{}>{})", group, inner)
+            }
+            None => format!("(?:{})", inner),
+        }
+    });
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(expanded.into_owned()),
+    }
+}
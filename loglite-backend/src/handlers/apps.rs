@@ -1,4 +1,5 @@
 use chrono::{DateTime, FixedOffset, Utc};
+use lazy_static::lazy_static;
 use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::State;
@@ -6,10 +7,38 @@ use sea_orm::prelude::DateTimeWithTimeZone;
 use sea_orm::{ActiveModelTrait, EntityTrait, QueryOrder, Set};
 
 use crate::entities::prelude::*;
-use crate::models::{AppInfo, CreateAppRequest};
+use crate::models::{AppInfo, CreateAppRequest, IngestEvent};
 use crate::state::AppState;
 use crate::utils::generate_app_id;
 
+/// What happens to events addressed to an app that does not exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownAppPolicy {
+    /// Refuse the events with 404.
+    Reject,
+    /// Create the app, named after the requested identifier.
+    Create,
+    /// Store the events in the quarantine app, tagged with the requested identifier.
+    Quarantine,
+}
+
+lazy_static! {
+    /// From `LOGLITE_UNKNOWN_APP_POLICY`: `reject` (the default), `create` or `quarantine`.
+    pub static ref UNKNOWN_APP_POLICY: UnknownAppPolicy =
+        match std::env::var("LOGLITE_UNKNOWN_APP_POLICY").as_deref() {
+            Ok("reject") | Err(_) => UnknownAppPolicy::Reject,
+            Ok("create") => UnknownAppPolicy::Create,
+            Ok("quarantine") => UnknownAppPolicy::Quarantine,
+            Ok(other) => {
+                tracing::warn!("Unknown LOGLITE_UNKNOWN_APP_POLICY {:?}, using reject", other);
+                UnknownAppPolicy::Reject
+            }
+        };
+    /// Name of the app receiving quarantined events, from `LOGLITE_QUARANTINE_APP`.
+    static ref QUARANTINE_APP: String =
+        std::env::var("LOGLITE_QUARANTINE_APP").unwrap_or_else(|_| "quarantine".to_string());
+}
+
 fn default_ts() -> DateTimeWithTimeZone {
     let utc: DateTime<Utc> = Utc::now();
     utc.with_timezone(&FixedOffset::east_opt(0).unwrap())
@@ -64,3 +93,113 @@ pub async fn list_apps(state: &State<AppState>) -> Result<Json<Vec<AppInfo>>, St
             .collect(),
     ))
 }
+
+/// Check that `app_id` names an existing application, failing with 404 otherwise.
+pub async fn require_app(state: &AppState, app_id: &str) -> Result<(), Status> {
+    App::find_by_id(app_id.to_string())
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .map(|_| ())
+        .ok_or(Status::NotFound)
+}
+
+/// Look up an app by `app_id`, or by a display name mapped through `generate_app_id`.
+async fn find_app(state: &AppState, name: &str) -> Result<Option<String>, Status> {
+    let db = state.db.as_ref();
+    for app_id in [name.to_string(), generate_app_id(name)] {
+        if App::find_by_id(app_id.clone())
+            .one(db)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .is_some()
+        {
+            return Ok(Some(app_id));
+        }
+    }
+    Ok(None)
+}
+
+/// Resolve an application for an external identifier, creating it if needed.
+///
+/// `name` may already be an `app_id`; otherwise it is treated as a display name and mapped
+/// through `generate_app_id`, so the same name always resolves to the same app.
+async fn ensure_app(state: &AppState, name: &str) -> Result<String, Status> {
+    if let Some(app_id) = find_app(state, name).await? {
+        return Ok(app_id);
+    }
+
+    let db = state.db.as_ref();
+    let app_id = generate_app_id(name);
+    let inserted = crate::entities::apps::ActiveModel {
+        app_id: Set(app_id.clone()),
+        name: Set(name.to_string()),
+        created_at: Set(default_ts()),
+    }
+    .insert(db)
+    .await;
+
+    match inserted {
+        Ok(_) => Ok(app_id),
+        // Another request may have created the app concurrently.
+        Err(_) => App::find_by_id(app_id.clone())
+            .one(db)
+            .await
+            .ok()
+            .flatten()
+            .map(|a| a.app_id)
+            .ok_or(Status::InternalServerError),
+    }
+}
+
+/// App that events addressed to some identifier are stored under.
+pub struct ResolvedApp {
+    pub app_id: String,
+    /// The requested identifier, when it was unknown and the events are quarantined.
+    pub quarantined_from: Option<String>,
+}
+
+impl ResolvedApp {
+    /// Copy of `events` tagged with `fields.original_app_id` when they are quarantined.
+    pub fn tag(&self, events: &[IngestEvent]) -> Option<Vec<IngestEvent>> {
+        let original = self.quarantined_from.as_ref()?;
+        Some(
+            events
+                .iter()
+                .map(|event| {
+                    let mut event = event.clone();
+                    if !event.fields.is_object() {
+                        event.fields = serde_json::json!({});
+                    }
+                    event.fields["original_app_id"] = original.clone().into();
+                    event
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Resolve the app events addressed to `name` go to, applying `UNKNOWN_APP_POLICY` when no
+/// app matches it. Unknown apps fail with 404 under the `reject` policy.
+pub async fn resolve_app(state: &AppState, name: &str) -> Result<ResolvedApp, Status> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let existing = match *UNKNOWN_APP_POLICY {
+        UnknownAppPolicy::Create => Some(ensure_app(state, name).await?),
+        _ => find_app(state, name).await?,
+    };
+    match (existing, *UNKNOWN_APP_POLICY) {
+        (Some(app_id), _) => Ok(ResolvedApp {
+            app_id,
+            quarantined_from: None,
+        }),
+        (None, UnknownAppPolicy::Quarantine) => Ok(ResolvedApp {
+            app_id: ensure_app(state, &QUARANTINE_APP).await?,
+            quarantined_from: Some(name.to_string()),
+        }),
+        (None, _) => Err(Status::NotFound),
+    }
+}
@@ -0,0 +1,99 @@
+use async_compression::tokio::bufread::{GzipDecoder, ZlibDecoder, ZstdDecoder};
+use rocket::data::{ByteUnit, Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+
+/// Compression applied to a request body, from its `Content-Encoding` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Identity,
+    Gzip,
+    /// HTTP `deflate`, i.e. a zlib stream.
+    Deflate,
+    Zstd,
+}
+
+/// Body encoding of a request, taken from its `Content-Type` and `Content-Encoding` headers.
+///
+/// Requests with a `Content-Encoding` other than gzip, deflate or zstd fail with 415.
+pub struct BodyEncoding {
+    pub json: bool,
+    pub compression: Compression,
+    /// Largest decompressed body accepted, from the `decompressed` limit (64 MiB by default).
+    pub max_expanded: ByteUnit,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BodyEncoding {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let json = req.content_type().is_some_and(|ct| ct.is_json());
+        let compression = match req.headers().get_one("Content-Encoding").map(str::trim) {
+            None | Some("") => Compression::Identity,
+            Some(e) if e.eq_ignore_ascii_case("identity") => Compression::Identity,
+            Some(e) if e.eq_ignore_ascii_case("gzip") || e.eq_ignore_ascii_case("x-gzip") => {
+                Compression::Gzip
+            }
+            Some(e) if e.eq_ignore_ascii_case("deflate") => Compression::Deflate,
+            Some(e) if e.eq_ignore_ascii_case("zstd") => Compression::Zstd,
+            Some(_) => return Outcome::Error((Status::UnsupportedMediaType, ())),
+        };
+        let max_expanded = req.limits().get("decompressed").unwrap_or(64.mebibytes());
+        Outcome::Success(BodyEncoding {
+            json,
+            compression,
+            max_expanded,
+        })
+    }
+}
+
+/// Read a raw request body of at most `limit` bytes, decompressing it according to `encoding`.
+///
+/// Compressed bodies are decoded as they stream in and rejected with 413 once the output
+/// exceeds `encoding.max_expanded`, so a small compressed payload cannot expand without bound.
+pub async fn read_body(
+    body: Data<'_>,
+    limit: ByteUnit,
+    encoding: &BodyEncoding,
+) -> Result<Vec<u8>, Status> {
+    let max = encoding.max_expanded;
+    match encoding.compression {
+        Compression::Identity => {
+            let raw = body
+                .open(limit)
+                .into_bytes()
+                .await
+                .map_err(|_| Status::BadRequest)?;
+            if !raw.is_complete() {
+                return Err(Status::PayloadTooLarge);
+            }
+            Ok(raw.into_inner())
+        }
+        Compression::Gzip => {
+            let mut decoder = GzipDecoder::new(BufReader::new(body.open(limit)));
+            decoder.multiple_members(true);
+            read_bounded(decoder, max).await
+        }
+        Compression::Deflate => {
+            read_bounded(ZlibDecoder::new(BufReader::new(body.open(limit))), max).await
+        }
+        Compression::Zstd => {
+            read_bounded(ZstdDecoder::new(BufReader::new(body.open(limit))), max).await
+        }
+    }
+}
+
+async fn read_bounded<R: AsyncRead + Unpin>(reader: R, max: ByteUnit) -> Result<Vec<u8>, Status> {
+    let mut out = Vec::new();
+    reader
+        .take(max.as_u64() + 1)
+        .read_to_end(&mut out)
+        .await
+        .map_err(|_| Status::BadRequest)?;
+    if out.len() as u64 > max.as_u64() {
+        return Err(Status::PayloadTooLarge);
+    }
+    Ok(out)
+}
@@ -0,0 +1,257 @@
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use crate::elastic::{document_to_event, index_app_name, parse_bulk_body};
+use crate::handlers::body::{read_body, BodyEncoding};
+use crate::handlers::ingest::ingest_resolved;
+use crate::handlers::resolve_app;
+use crate::models::IngestEvent;
+use crate::state::AppState;
+use crate::store::stored_keys;
+
+/// Result of a single bulk item, rendered into the `items` array of the response.
+struct BulkItemResult {
+    action: String,
+    index: String,
+    /// The document `_id`; items without one get no `_id`, as the event id is only assigned
+    /// once the event is written.
+    id: Option<String>,
+    status: u16,
+    error: Option<(&'static str, String)>,
+}
+
+impl BulkItemResult {
+    fn to_json(&self) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "_index": self.index,
+            "status": self.status,
+        });
+        if let Some(id) = &self.id {
+            body["_id"] = serde_json::json!(id);
+        }
+        match &self.error {
+            Some((kind, reason)) => {
+                body["error"] = serde_json::json!({ "type": kind, "reason": reason });
+            }
+            None => {
+                body["_version"] = serde_json::json!(1);
+                body["result"] = serde_json::json!("created");
+                body["_shards"] = serde_json::json!({ "total": 1, "successful": 1, "failed": 0 });
+            }
+        }
+        serde_json::json!({ self.action.clone(): body })
+    }
+}
+
+/// Minimal cluster info, answered on `GET /` because shippers probe it before writing.
+#[get("/")]
+pub async fn elastic_info() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "name": "loglite",
+        "cluster_name": "loglite",
+        "version": {
+            "number": "8.11.0",
+            "build_flavor": "default",
+            "lucene_version": "9.8.0",
+            "minimum_wire_compatibility_version": "7.17.0",
+            "minimum_index_compatibility_version": "7.0.0"
+        },
+        "tagline": "You Know, for Search"
+    }))
+}
+
+/// Elasticsearch-compatible `_bulk` endpoint.
+#[post("/_bulk", data = "<body>")]
+pub async fn elastic_bulk(
+    state: &State<AppState>,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<Json<serde_json::Value>, Status> {
+    handle_bulk(state.inner(), None, encoding, limits, body).await
+}
+
+/// Elasticsearch-compatible `_bulk` endpoint with a default target index.
+#[post("/<index>/_bulk", data = "<body>")]
+pub async fn elastic_bulk_index(
+    state: &State<AppState>,
+    index: &str,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<Json<serde_json::Value>, Status> {
+    handle_bulk(state.inner(), Some(index), encoding, limits, body).await
+}
+
+/// Ingest `index`/`create` documents and report a per-item status.
+///
+/// Each target index names an app, with daily date suffixes stripped; indexes rejected by the
+/// unknown-app policy get a 404. A `create` whose `_id` was already stored gets a 409
+/// `version_conflict_engine_exception`. `update` and `delete` are answered with per-item
+/// errors since stored events are immutable. Items whose batch fails to persist get a 503 so
+/// shippers retry them.
+async fn handle_bulk(
+    state: &AppState,
+    default_index: Option<&str>,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<Json<serde_json::Value>, Status> {
+    let started = Instant::now();
+    let limit = limits.get("bulk").unwrap_or(32.mebibytes());
+    let raw = read_body(body, limit, &encoding).await?;
+    let text = String::from_utf8(raw).map_err(|_| Status::BadRequest)?;
+    let ops = parse_bulk_body(&text).map_err(|_| Status::BadRequest)?;
+
+    let mut results: Vec<BulkItemResult> = Vec::with_capacity(ops.len());
+    let mut batches: HashMap<String, (Vec<usize>, Vec<IngestEvent>)> = HashMap::new();
+
+    for op in ops {
+        let index = op
+            .index
+            .clone()
+            .or_else(|| default_index.map(|s| s.to_string()))
+            .unwrap_or_default();
+        let mut result = BulkItemResult {
+            action: op.action.clone(),
+            index: index.clone(),
+            id: op.id.clone(),
+            status: 201,
+            error: None,
+        };
+
+        if op.action != "index" && op.action != "create" {
+            result.status = 400;
+            result.error = Some((
+                "action_request_validation_exception",
+                format!("action [{}] is not supported", op.action),
+            ));
+            results.push(result);
+            continue;
+        }
+        if index.is_empty() {
+            result.status = 400;
+            result.error = Some((
+                "action_request_validation_exception",
+                "index is missing".to_string(),
+            ));
+            results.push(result);
+            continue;
+        }
+
+        let document = op.document.as_ref().unwrap_or(&serde_json::Value::Null);
+        let mut event = match document_to_event(document, &index) {
+            Ok(event) => event,
+            Err(reason) => {
+                result.status = 400;
+                result.error = Some(("document_parsing_exception", reason));
+                results.push(result);
+                continue;
+            }
+        };
+        // A document `_id` makes retried bulk requests idempotent, as in Elasticsearch.
+        event.idempotency_key = op.id.as_ref().map(|id| format!("{}/{}", index, id));
+
+        let batch = batches
+            .entry(index_app_name(&index).to_string())
+            .or_default();
+        batch.0.push(results.len());
+        batch.1.push(event);
+        results.push(result);
+    }
+
+    for (app, (positions, events)) in batches {
+        let error = match ingest_bulk_batch(state, &app, &positions, events, &mut results).await {
+            Ok(()) => continue,
+            Err(e) if e == Status::NotFound => (
+                404,
+                "index_not_found_exception",
+                format!("no such index [{}]", app),
+            ),
+            Err(e) if e == Status::TooManyRequests => (
+                429,
+                "es_rejected_execution_exception",
+                format!("index [{}] is over its ingest quota", app),
+            ),
+            Err(_) => (
+                503,
+                "unavailable_shards_exception",
+                "failed to persist events".to_string(),
+            ),
+        };
+        for &pos in &positions {
+            if results[pos].error.is_none() {
+                results[pos].status = error.0;
+                results[pos].error = Some((error.1, error.2.clone()));
+            }
+        }
+    }
+
+    let errors = results.iter().any(|r| r.error.is_some());
+    Ok(Json(serde_json::json!({
+        "took": started.elapsed().as_millis() as u64,
+        "errors": errors,
+        "items": results.iter().map(|r| r.to_json()).collect::<Vec<_>>(),
+    })))
+}
+
+/// Store the events of one app's bulk items, after answering `create` items whose `_id` is
+/// already stored (or repeated in the request) with a 409.
+async fn ingest_bulk_batch(
+    state: &AppState,
+    app: &str,
+    positions: &[usize],
+    events: Vec<IngestEvent>,
+    results: &mut [BulkItemResult],
+) -> Result<(), Status> {
+    let resolved = resolve_app(state, app).await.map_err(|status| {
+        if status == Status::NotFound {
+            status
+        } else {
+            Status::ServiceUnavailable
+        }
+    })?;
+
+    let create_keys: Vec<&str> = positions
+        .iter()
+        .zip(&events)
+        .filter(|(&pos, _)| results[pos].action == "create")
+        .filter_map(|(_, e)| e.idempotency_key.as_deref())
+        .collect();
+    let stored = if create_keys.is_empty() {
+        HashSet::new()
+    } else {
+        stored_keys(state, &resolved.app_id, &create_keys)
+            .await
+            .map_err(|_| Status::ServiceUnavailable)?
+    };
+
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut kept = Vec::with_capacity(events.len());
+    for (&pos, event) in positions.iter().zip(events) {
+        let result = &mut results[pos];
+        if let (Some(key), "create") = (&event.idempotency_key, result.action.as_str()) {
+            if stored.contains(key) || !seen.insert(key.clone()) {
+                result.status = 409;
+                result.error = Some((
+                    "version_conflict_engine_exception",
+                    format!(
+                        "[{}]: version conflict, document already exists",
+                        result.id.as_deref().unwrap_or_default()
+                    ),
+                ));
+                continue;
+            }
+        }
+        kept.push(event);
+    }
+
+    ingest_resolved(state, &resolved, &kept)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.status)
+}
@@ -0,0 +1,202 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+
+use super::apps::require_app;
+use crate::entities::prelude::*;
+use crate::filter::{CompiledFilter, Condition, FilterAction};
+use crate::models::{
+    CreateFilterRuleRequest, FilterRuleInfo, FilterStats, UpdateFilterRuleRequest,
+};
+use crate::state::AppState;
+
+fn default_ts() -> DateTimeWithTimeZone {
+    let utc: DateTime<Utc> = Utc::now();
+    utc.with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+fn to_info(model: crate::entities::filter_rules::Model) -> FilterRuleInfo {
+    FilterRuleInfo {
+        id: model.id,
+        app_id: model.app_id,
+        source_id: model.source_id,
+        name: model.name,
+        condition: model.condition,
+        action: model.action,
+        enabled: model.enabled,
+        created_at: model.created_at,
+    }
+}
+
+/// Compile a rule to validate it, returning its condition and action as stored JSON.
+fn validate(
+    condition: &Condition,
+    action: &FilterAction,
+) -> Result<(serde_json::Value, serde_json::Value), Status> {
+    CompiledFilter::compile(condition.clone(), action.clone())
+        .map_err(|_| Status::UnprocessableEntity)?;
+    let condition = serde_json::to_value(condition).map_err(|_| Status::InternalServerError)?;
+    let action = serde_json::to_value(action).map_err(|_| Status::InternalServerError)?;
+    Ok((condition, action))
+}
+
+async fn reload(state: &AppState) -> Result<(), Status> {
+    state.filters.reload(state.db.as_ref()).await.map_err(|e| {
+        tracing::error!("Failed to reload filter rules: {}", e);
+        Status::InternalServerError
+    })
+}
+
+/// Create a filter rule for an application, or for one of its sources.
+///
+/// An invalid pattern or a zero `keep_one_in` is rejected with 422.
+#[post("/filters", data = "<payload>")]
+pub async fn create_filter_rule(
+    state: &State<AppState>,
+    payload: Json<CreateFilterRuleRequest>,
+) -> Result<Json<FilterRuleInfo>, Status> {
+    require_app(state, &payload.app_id).await?;
+    if let Some(source_id) = payload.source_id {
+        let source = AppSource::find_by_id(source_id)
+            .one(state.db.as_ref())
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+        if source.is_none_or(|s| s.app_id != payload.app_id) {
+            return Err(Status::UnprocessableEntity);
+        }
+    }
+    let (condition, action) = validate(&payload.condition, &payload.action)?;
+
+    let model = crate::entities::filter_rules::ActiveModel {
+        id: Set(0), // Auto-increment
+        app_id: Set(payload.app_id.clone()),
+        source_id: Set(payload.source_id),
+        name: Set(payload.name.clone()),
+        condition: Set(condition),
+        action: Set(action),
+        enabled: Set(payload.enabled.unwrap_or(true)),
+        created_at: Set(default_ts()),
+    }
+    .insert(state.db.as_ref())
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(model)))
+}
+
+/// List all filter rules, optionally for one application.
+#[get("/filters?<app_id>")]
+pub async fn list_filter_rules(
+    state: &State<AppState>,
+    app_id: Option<String>,
+) -> Result<Json<Vec<FilterRuleInfo>>, Status> {
+    let mut query = FilterRule::find();
+
+    if let Some(app_id) = app_id {
+        query = query.filter(crate::entities::filter_rules::Column::AppId.eq(app_id));
+    }
+
+    let rules = query
+        .order_by_asc(crate::entities::filter_rules::Column::Id)
+        .all(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(rules.into_iter().map(to_info).collect()))
+}
+
+/// Events dropped since startup, in total and per rule.
+#[get("/filters/stats")]
+pub async fn filter_stats(state: &State<AppState>) -> Result<Json<FilterStats>, Status> {
+    let rules = FilterRule::find()
+        .order_by_asc(crate::entities::filter_rules::Column::Id)
+        .all(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(state.filters.stats(rules)))
+}
+
+/// Get a single filter rule by ID.
+#[get("/filters/<id>")]
+pub async fn get_filter_rule(
+    state: &State<AppState>,
+    id: i64,
+) -> Result<Json<FilterRuleInfo>, Status> {
+    let rule = FilterRule::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(to_info(rule)))
+}
+
+/// Update a filter rule.
+#[put("/filters/<id>", data = "<payload>")]
+pub async fn update_filter_rule(
+    state: &State<AppState>,
+    id: i64,
+    payload: Json<UpdateFilterRuleRequest>,
+) -> Result<Json<FilterRuleInfo>, Status> {
+    let rule = FilterRule::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    let condition = match &payload.condition {
+        Some(condition) => condition.clone(),
+        None => serde_json::from_value(rule.condition.clone())
+            .map_err(|_| Status::InternalServerError)?,
+    };
+    let action = match &payload.action {
+        Some(action) => action.clone(),
+        None => {
+            serde_json::from_value(rule.action.clone()).map_err(|_| Status::InternalServerError)?
+        }
+    };
+    let (condition, action) = validate(&condition, &action)?;
+
+    let mut active: crate::entities::filter_rules::ActiveModel = rule.into();
+
+    if let Some(name) = &payload.name {
+        active.name = Set(name.clone());
+    }
+    active.condition = Set(condition);
+    active.action = Set(action);
+    if let Some(enabled) = payload.enabled {
+        active.enabled = Set(enabled);
+    }
+
+    let updated = active
+        .update(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(updated)))
+}
+
+/// Delete a filter rule.
+#[delete("/filters/<id>")]
+pub async fn delete_filter_rule(state: &State<AppState>, id: i64) -> Result<Status, Status> {
+    let rule = FilterRule::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    let active: crate::entities::filter_rules::ActiveModel = rule.into();
+    active
+        .delete(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Status::NoContent)
+}
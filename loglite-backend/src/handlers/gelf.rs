@@ -0,0 +1,40 @@
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use std::net::IpAddr;
+
+use crate::gelf::{decode_gelf_payload, default_app_id, MAX_MESSAGE_BYTES};
+use crate::handlers::body::{read_body, BodyEncoding};
+use crate::handlers::ingest::IngestFailure;
+use crate::handlers::ingest_events_for_app;
+use crate::models::IngestResponse;
+use crate::state::AppState;
+
+/// Ingest a single GELF message over HTTP.
+///
+/// The body may be plain, gzip or zlib compressed JSON, announced by `Content-Encoding` or
+/// detected from its magic bytes; its size is capped by the `gelf` limit (8 MiB by default).
+/// Events go to `app_id` when given, otherwise to `LOGLITE_GELF_APP_ID`.
+#[post("/gelf?<app_id>", data = "<body>")]
+pub async fn ingest_gelf(
+    state: &State<AppState>,
+    app_id: Option<String>,
+    client_ip: Option<IpAddr>,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<Json<IngestResponse>, IngestFailure> {
+    let limit = limits.get("gelf").unwrap_or(MAX_MESSAGE_BYTES.bytes());
+    let raw = read_body(body, limit, &encoding).await?;
+
+    let peer_host = client_ip.map(|ip| ip.to_string()).unwrap_or_default();
+    let event = decode_gelf_payload(&raw, &peer_host).map_err(|_| Status::BadRequest)?;
+
+    let app_id = app_id.unwrap_or_else(default_app_id);
+    let accepted = ingest_events_for_app(state.inner(), &app_id, &[event]).await?;
+    Ok(Json(IngestResponse {
+        accepted,
+        ..Default::default()
+    }))
+}
@@ -0,0 +1,403 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Json;
+use rocket::State;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::handlers::body::{read_body, BodyEncoding};
+use crate::handlers::{ingest_resolved, resolve_app};
+use crate::models::{HecResponse, IngestEvent};
+use crate::state::AppState;
+use crate::utils::level_to_severity;
+
+/// A HEC JSON reply, with `Retry-After` set when the app is over its quota.
+pub struct HecResult {
+    status: Status,
+    body: HecResponse,
+    retry_after: Option<u64>,
+}
+
+impl<'r> Responder<'r, 'static> for HecResult {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build_from(Json(self.body).respond_to(req)?);
+        response.status(self.status);
+        if let Some(secs) = self.retry_after {
+            response.raw_header("Retry-After", secs.to_string());
+        }
+        response.ok()
+    }
+}
+
+fn hec_reply(status: Status, code: u8, text: &'static str) -> HecResult {
+    HecResult {
+        status,
+        body: HecResponse {
+            text,
+            code,
+            invalid_event_number: None,
+        },
+        retry_after: None,
+    }
+}
+
+fn invalid_event(code: u8, text: &'static str, index: usize) -> HecResult {
+    HecResult {
+        status: Status::BadRequest,
+        body: HecResponse {
+            text,
+            code,
+            invalid_event_number: Some(index),
+        },
+        retry_after: None,
+    }
+}
+
+fn default_ts() -> DateTimeWithTimeZone {
+    Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+/// HEC token from an `Authorization: Splunk <token>` header.
+pub struct HecToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for HecToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|v| v.strip_prefix("Splunk "))
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty());
+        Outcome::Success(HecToken(token))
+    }
+}
+
+/// Configured HEC tokens and the app each one writes to by default.
+///
+/// `LOGLITE_HEC_TOKENS` uses the form `<token>=<app_id>,<token>=<app_id>`.
+fn hec_tokens() -> HashMap<String, String> {
+    std::env::var("LOGLITE_HEC_TOKENS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| {
+            let (token, app_id) = pair.split_once('=')?;
+            let (token, app_id) = (token.trim(), app_id.trim());
+            if token.is_empty() || app_id.is_empty() {
+                return None;
+            }
+            Some((token.to_string(), app_id.to_string()))
+        })
+        .collect()
+}
+
+/// Resolve the token's default app, or the HEC error reply for a missing/unknown token.
+fn authorize(token: &HecToken) -> Result<String, HecResult> {
+    let token = token
+        .0
+        .as_ref()
+        .ok_or_else(|| hec_reply(Status::Unauthorized, 2, "Token is required"))?;
+    hec_tokens()
+        .remove(token)
+        .ok_or_else(|| hec_reply(Status::Forbidden, 4, "Invalid token"))
+}
+
+/// Parse HEC `time`, which is epoch seconds as a number or string.
+fn parse_hec_time(v: &serde_json::Value) -> Option<DateTimeWithTimeZone> {
+    let secs = match v {
+        serde_json::Value::Number(n) => n.as_f64()?,
+        serde_json::Value::String(s) => s.parse::<f64>().ok()?,
+        _ => return None,
+    };
+    let whole = secs.floor();
+    let utc = DateTime::from_timestamp(whole as i64, ((secs - whole) * 1e9) as u32)?;
+    Some(utc.with_timezone(&FixedOffset::east_opt(0).unwrap()))
+}
+
+/// Metadata defaults taken from the query string.
+#[derive(Default)]
+struct HecDefaults {
+    index: Option<String>,
+    host: Option<String>,
+    source: Option<String>,
+    sourcetype: Option<String>,
+    time: Option<String>,
+}
+
+/// App named by an event's `index`, or the token's app without one.
+fn app_for_index(index: Option<&str>, token_app: &str) -> String {
+    index
+        .filter(|i| !i.is_empty())
+        .unwrap_or(token_app)
+        .to_string()
+}
+
+/// Key prefix for the events of a request body; each event appends its position, so a
+/// retried request stores nothing new.
+fn body_key(raw: &[u8]) -> String {
+    format!("hec:{}", hex::encode(Sha256::digest(raw)))
+}
+
+/// Store events per app, resolving every index before anything is written.
+async fn store(state: &AppState, by_app: HashMap<String, Vec<IngestEvent>>) -> HecResult {
+    let mut resolved = Vec::with_capacity(by_app.len());
+    for (app, events) in &by_app {
+        match resolve_app(state, app).await {
+            Ok(app) => resolved.push((app, events)),
+            Err(status) if status == Status::NotFound => {
+                return hec_reply(Status::BadRequest, 7, "Incorrect index");
+            }
+            Err(_) => return hec_reply(Status::ServiceUnavailable, 9, "Server is busy"),
+        }
+    }
+
+    for (app, events) in &resolved {
+        match ingest_resolved(state, app, events).await {
+            Ok(_) => {}
+            Err(e) if e.status == Status::NotFound => {
+                return hec_reply(Status::BadRequest, 7, "Incorrect index");
+            }
+            Err(e) if e.status == Status::TooManyRequests => {
+                return HecResult {
+                    retry_after: e.retry_after,
+                    ..hec_reply(Status::TooManyRequests, 9, "Server is busy")
+                };
+            }
+            Err(_) => return hec_reply(Status::ServiceUnavailable, 9, "Server is busy"),
+        }
+    }
+    hec_reply(Status::Ok, 0, "Success")
+}
+
+/// Parse and store a HEC event request.
+///
+/// The body is a stream of JSON envelopes (`{"event": ..., "time": ..., "host": ...}`),
+/// optionally compressed. `index` selects the app, subject to the unknown-app policy,
+/// falling back to the token's app; `sourcetype`, `source`, `host` and `time` map onto the
+/// event columns. Indexed `fields` and object events are stored in `fields`. Events are keyed
+/// on the body and their position in it.
+async fn collect_events(
+    state: &AppState,
+    token: HecToken,
+    defaults: HecDefaults,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> HecResult {
+    let token_app = match authorize(&token) {
+        Ok(app_id) => app_id,
+        Err(reply) => return reply,
+    };
+
+    let limit = limits.get("hec").unwrap_or(16.mebibytes());
+    let raw = match read_body(body, limit, &encoding).await {
+        Ok(raw) => raw,
+        Err(_) => return hec_reply(Status::BadRequest, 6, "Invalid data format"),
+    };
+    if raw.iter().all(|b| b.is_ascii_whitespace()) {
+        return hec_reply(Status::BadRequest, 5, "No data");
+    }
+
+    let key = body_key(&raw);
+    let mut by_app: HashMap<String, Vec<IngestEvent>> = HashMap::new();
+    let stream = serde_json::Deserializer::from_slice(&raw).into_iter::<serde_json::Value>();
+
+    for (n, envelope) in stream.enumerate() {
+        let Ok(serde_json::Value::Object(envelope)) = envelope else {
+            return invalid_event(6, "Invalid data format", n);
+        };
+        let event = match envelope.get("event") {
+            None => return invalid_event(12, "Event field is required", n),
+            Some(serde_json::Value::String(s)) if s.is_empty() => {
+                return invalid_event(13, "Event field cannot be blank", n)
+            }
+            Some(serde_json::Value::Null) => {
+                return invalid_event(13, "Event field cannot be blank", n)
+            }
+            Some(event) => event,
+        };
+        let meta = |key: &str, default: &Option<String>| {
+            envelope
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .or_else(|| default.clone())
+        };
+
+        let ts = match envelope.get("time") {
+            Some(t) => match parse_hec_time(t) {
+                Some(ts) => ts,
+                None => return invalid_event(6, "Invalid data format", n),
+            },
+            None => defaults
+                .time
+                .as_ref()
+                .and_then(|t| parse_hec_time(&serde_json::Value::String(t.clone())))
+                .unwrap_or_else(default_ts),
+        };
+
+        let mut fields = match envelope.get("fields") {
+            Some(serde_json::Value::Object(f)) => f.clone(),
+            _ => serde_json::Map::new(),
+        };
+        let message = match event {
+            serde_json::Value::String(s) => s.clone(),
+            other => {
+                fields.insert("event".to_string(), other.clone());
+                other.to_string()
+            }
+        };
+        let severity = ["level", "severity"]
+            .iter()
+            .find_map(|k| {
+                fields
+                    .get(*k)
+                    .or_else(|| event.get(*k))
+                    .and_then(|v| v.as_str())
+            })
+            .and_then(level_to_severity);
+
+        let index = meta("index", &defaults.index);
+        let app_id = app_for_index(index.as_deref(), &token_app);
+
+        by_app.entry(app_id).or_default().push(IngestEvent {
+            ts,
+            host: meta("host", &defaults.host).unwrap_or_default(),
+            source: meta("source", &defaults.source).unwrap_or_else(|| "http:hec".to_string()),
+            sourcetype: meta("sourcetype", &defaults.sourcetype),
+            severity,
+            message,
+            fields: serde_json::Value::Object(fields),
+            idempotency_key: Some(format!("{}:{}", key, n)),
+        });
+    }
+
+    store(state, by_app).await
+}
+
+/// HEC event endpoint (`/services/collector/event`).
+#[post(
+    "/services/collector/event?<index>&<host>&<source>&<sourcetype>",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn hec_event(
+    state: &State<AppState>,
+    token: HecToken,
+    index: Option<String>,
+    host: Option<String>,
+    source: Option<String>,
+    sourcetype: Option<String>,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> HecResult {
+    let defaults = HecDefaults {
+        index,
+        host,
+        source,
+        sourcetype,
+        time: None,
+    };
+    collect_events(state.inner(), token, defaults, encoding, limits, body).await
+}
+
+/// HEC event endpoint under its short alias (`/services/collector`).
+#[post(
+    "/services/collector?<index>&<host>&<source>&<sourcetype>",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn hec_collector(
+    state: &State<AppState>,
+    token: HecToken,
+    index: Option<String>,
+    host: Option<String>,
+    source: Option<String>,
+    sourcetype: Option<String>,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> HecResult {
+    let defaults = HecDefaults {
+        index,
+        host,
+        source,
+        sourcetype,
+        time: None,
+    };
+    collect_events(state.inner(), token, defaults, encoding, limits, body).await
+}
+
+/// HEC raw endpoint (`/services/collector/raw`).
+///
+/// Every non-empty line of the body becomes one event, keyed on the body and its line number.
+/// Metadata comes from the query string.
+#[post(
+    "/services/collector/raw?<index>&<host>&<source>&<sourcetype>&<time>",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn hec_raw(
+    state: &State<AppState>,
+    token: HecToken,
+    index: Option<String>,
+    host: Option<String>,
+    source: Option<String>,
+    sourcetype: Option<String>,
+    time: Option<String>,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> HecResult {
+    let token_app = match authorize(&token) {
+        Ok(app_id) => app_id,
+        Err(reply) => return reply,
+    };
+
+    let limit = limits.get("hec").unwrap_or(16.mebibytes());
+    let raw = match read_body(body, limit, &encoding).await {
+        Ok(raw) => raw,
+        Err(_) => return hec_reply(Status::BadRequest, 6, "Invalid data format"),
+    };
+    let text = String::from_utf8_lossy(&raw);
+
+    let ts = time
+        .and_then(|t| parse_hec_time(&serde_json::Value::String(t)))
+        .unwrap_or_else(default_ts);
+    let app_id = app_for_index(index.as_deref(), &token_app);
+    let key = body_key(&raw);
+
+    let events: Vec<IngestEvent> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, line)| IngestEvent {
+            ts,
+            host: host.clone().unwrap_or_default(),
+            source: source.clone().unwrap_or_else(|| "http:hec".to_string()),
+            sourcetype: sourcetype.clone(),
+            severity: None,
+            message: line.to_string(),
+            fields: serde_json::json!({}),
+            idempotency_key: Some(format!("{}:{}", key, i)),
+        })
+        .collect();
+    if events.is_empty() {
+        return hec_reply(Status::BadRequest, 5, "No data");
+    }
+
+    store(state.inner(), HashMap::from([(app_id, events)])).await
+}
+
+/// HEC health endpoint, polled by clients before sending.
+#[get("/services/collector/health")]
+pub async fn hec_health() -> HecResult {
+    hec_reply(Status::Ok, 17, "HEC is healthy")
+}
@@ -1,257 +1,653 @@
-use chrono::{DateTime, FixedOffset, Utc};
+use lazy_static::lazy_static;
+use rocket::data::{Data, Limits, ToByteUnit};
 use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
 use rocket::serde::json::Json;
 use rocket::State;
 use sea_orm::prelude::DateTimeWithTimeZone;
-use sea_orm::{ActiveModelTrait, Set};
-use tantivy::doc;
 
-use crate::models::{IngestEvent, IngestRequest, IngestResponse};
+use super::apps::{resolve_app, ResolvedApp};
+use super::body::{read_body, BodyEncoding};
+use crate::access_log::AccessLogFormat;
+use crate::db::DEFAULT_APP;
+use crate::models::{IngestError, IngestEvent, IngestRequest, IngestResponse};
+use crate::quota::QuotaExceeded;
 use crate::state::AppState;
+use crate::store::accept_events;
 use crate::utils::{
     detect_log_format, level_to_severity, merge_multiline_logs, parse_nginx_access_line, LogFormat,
 };
+use crate::wal::WalFull;
 
-fn default_ts() -> DateTimeWithTimeZone {
-    let utc: DateTime<Utc> = Utc::now();
-    utc.with_timezone(&FixedOffset::east_opt(0).unwrap())
+/// Most rejection reasons listed in one response.
+pub const MAX_REPORTED_ERRORS: usize = 1000;
+
+lazy_static! {
+    /// Largest accepted event (message plus fields) or text line, from
+    /// `LOGLITE_MAX_EVENT_BYTES`.
+    pub static ref MAX_EVENT_BYTES: usize = std::env::var("LOGLITE_MAX_EVENT_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(1024 * 1024);
+}
+
+/// Why events were not ingested: the status to answer with and, when the app is over its
+/// quota, the seconds to advertise in `Retry-After`.
+#[derive(Debug)]
+pub struct IngestFailure {
+    pub status: Status,
+    pub retry_after: Option<u64>,
+}
+
+impl From<Status> for IngestFailure {
+    fn from(status: Status) -> Self {
+        Self {
+            status,
+            retry_after: None,
+        }
+    }
+}
+
+impl std::fmt::Display for IngestFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.status)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for IngestFailure {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        // Errors are forwarded to the catchers, which would drop the header.
+        match self.retry_after {
+            Some(secs) => Response::build()
+                .status(self.status)
+                .raw_header("Retry-After", secs.to_string())
+                .ok(),
+            None => Err(self.status),
+        }
+    }
 }
 
 /// Ingest events for a single application.
 ///
-/// This function writes events to Postgres and mirrors the searchable fields into Tantivy.
-async fn ingest_events_for_app(
+/// `app` is an `app_id` or app name; unknown apps are handled by the unknown-app policy, so
+/// this fails with 404 when they are rejected. Events are acknowledged once they are durable
+/// in the ingestion WAL (or written to Postgres when the WAL is disabled) and become
+/// searchable at the next group commit of the Tantivy index. A full queue is reported as 503,
+/// and an app over its quota as 429 with `Retry-After`.
+pub async fn ingest_events_for_app(
     state: &AppState,
-    app_id: &str,
+    app: &str,
+    events: &[IngestEvent],
+) -> Result<usize, IngestFailure> {
+    let resolved = resolve_app(state, app).await?;
+    ingest_resolved(state, &resolved, events).await
+}
+
+/// Ingest events for an app already resolved with `resolve_app`.
+pub async fn ingest_resolved(
+    state: &AppState,
+    app: &ResolvedApp,
     events: &[IngestEvent],
-) -> Result<usize, Status> {
-    if events.is_empty() {
-        return Ok(0);
+) -> Result<usize, IngestFailure> {
+    let tagged = app.tag(events);
+    accept_events(
+        state,
+        &app.app_id,
+        None,
+        tagged.as_deref().unwrap_or(events),
+    )
+    .await
+    .map_err(|e| {
+        if let Some(exceeded) = e.downcast_ref::<QuotaExceeded>() {
+            IngestFailure {
+                status: Status::TooManyRequests,
+                retry_after: Some(exceeded.retry_after),
+            }
+        } else if e.is::<WalFull>() {
+            Status::ServiceUnavailable.into()
+        } else {
+            Status::InternalServerError.into()
+        }
+    })
+}
+
+/// Batch idempotency key from the `Idempotency-Key` header.
+///
+/// Each event without its own key is keyed by the batch key and its position in the batch,
+/// so re-sending the same batch stores nothing new.
+pub struct BatchKey(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BatchKey {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let key = req
+            .headers()
+            .get_one("Idempotency-Key")
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(|k| k.to_string());
+        Outcome::Success(BatchKey(key))
+    }
+}
+
+impl BatchKey {
+    /// Key for the event at `index` in the batch.
+    pub fn key_for(&self, index: usize) -> Option<String> {
+        self.0.as_ref().map(|key| format!("{}:{}", key, index))
+    }
+
+    /// Key every event that does not carry its own idempotency key.
+    pub fn apply(&self, events: &mut [IngestEvent]) {
+        for (i, e) in events.iter_mut().enumerate() {
+            if e.idempotency_key.is_none() {
+                e.idempotency_key = self.key_for(i);
+            }
+        }
     }
+}
+
+/// How a batch containing invalid events is handled, from the `mode` query parameter.
+///
+/// `strict` (the default) stores nothing if any event is invalid; `partial` stores the valid
+/// events and reports the rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngestMode {
+    Strict,
+    Partial,
+}
 
-    let db = &state.db;
-    let mut docs: Vec<tantivy::TantivyDocument> = Vec::with_capacity(events.len());
-
-    for e in events {
-        let inserted = crate::entities::events::ActiveModel {
-            id: Set(state.ids.next_id()),
-            app_id: Set(app_id.to_string()),
-            ts: Set(e.ts),
-            host: Set(e.host.clone()),
-            source: Set(e.source.clone()),
-            sourcetype: Set(e.sourcetype.clone()),
-            severity: Set(e.severity),
-            message: Set(e.message.clone()),
-            fields: Set(e.fields.clone()),
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IngestMode {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.query_value::<&str>("mode").and_then(|v| v.ok()) {
+            None | Some("strict") => Outcome::Success(IngestMode::Strict),
+            Some("partial") => Outcome::Success(IngestMode::Partial),
+            Some(_) => Outcome::Error((Status::BadRequest, ())),
         }
-        .insert(db.as_ref())
-        .await
-        .map_err(|_| Status::InternalServerError)?;
-
-        let ts_epoch_ms = inserted.ts.timestamp_millis();
-
-        docs.push(doc!(
-            state.search.field_app_id => inserted.app_id.clone(),
-            state.search.field_event_id => inserted.id,
-            state.search.field_ts_epoch_ms => ts_epoch_ms,
-            state.search.field_host => inserted.host,
-            state.search.field_source => inserted.source,
-            state.search.field_message => inserted.message
+    }
+}
+
+/// Check a decoded event before it is stored.
+fn check_event(e: &IngestEvent) -> Result<(), String> {
+    if e.message.trim().is_empty() {
+        return Err("empty message".to_string());
+    }
+    let size = e.message.len() + e.fields.to_string().len();
+    if size > *MAX_EVENT_BYTES {
+        return Err(format!(
+            "event is {} bytes, over the {} byte limit",
+            size, *MAX_EVENT_BYTES
         ));
     }
+    Ok(())
+}
 
-    {
-        let mut writer = state.search.writer.lock();
-        for d in docs {
-            writer
-                .add_document(d)
-                .map_err(|_| Status::InternalServerError)?;
+/// Decode and check one JSON event.
+pub fn decode_event(value: serde_json::Value) -> Result<IngestEvent, String> {
+    if !value.is_object() {
+        return Err("event must be a JSON object".to_string());
+    }
+    if let Some(ts) = value.get("ts").filter(|ts| !ts.is_null()) {
+        if serde_json::from_value::<DateTimeWithTimeZone>(ts.clone()).is_err() {
+            return Err(format!("invalid timestamp: {}", ts));
         }
-        writer.commit().map_err(|_| Status::InternalServerError)?;
     }
+    let event: IngestEvent =
+        serde_json::from_value(value).map_err(|e| format!("invalid event: {}", e))?;
+    check_event(&event)?;
+    Ok(event)
+}
 
-    state
-        .search
-        .reader
-        .reload()
-        .map_err(|_| Status::InternalServerError)?;
+/// Record a rejection in `report`, listing at most `MAX_REPORTED_ERRORS` reasons.
+pub fn reject(report: &mut IngestResponse, index: Option<usize>, reason: String) {
+    report.rejected += 1;
+    if report.errors.len() < MAX_REPORTED_ERRORS {
+        report.errors.push(IngestError { index, reason });
+    }
+}
+
+/// Split a text body into lines, rejecting lines over `MAX_EVENT_BYTES`.
+fn checked_lines<'a>(body: &'a str, report: &mut IngestResponse) -> Vec<&'a str> {
+    body.lines()
+        .enumerate()
+        .filter(|(i, line)| {
+            if line.len() > *MAX_EVENT_BYTES {
+                reject(
+                    report,
+                    Some(*i),
+                    format!(
+                        "line is {} bytes, over the {} byte limit",
+                        line.len(),
+                        *MAX_EVENT_BYTES
+                    ),
+                );
+                false
+            } else {
+                true
+            }
+        })
+        .map(|(_, line)| line)
+        .collect()
+}
+
+/// Store the valid events of a batch according to `mode` and build the response.
+///
+/// `report` already holds the rejections found while decoding. In strict mode any rejection
+/// stores nothing and answers 400; an unknown app rejected by the unknown-app policy rejects
+/// the whole batch with 404.
+async fn store_batch(
+    state: &AppState,
+    app_id: &str,
+    events: Vec<IngestEvent>,
+    mut report: IngestResponse,
+    mode: IngestMode,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let app = match resolve_app(state, app_id).await {
+        Ok(app) => app,
+        Err(status) if status == Status::NotFound => {
+            report.rejected += events.len();
+            report.errors.insert(
+                0,
+                IngestError {
+                    index: None,
+                    reason: format!("unknown app: {}", app_id),
+                },
+            );
+            report.errors.truncate(MAX_REPORTED_ERRORS);
+            return Ok((Status::NotFound, Json(report)));
+        }
+        Err(status) => return Err(status.into()),
+    };
 
-    Ok(events.len())
+    if report.rejected > 0 && mode == IngestMode::Strict {
+        return Ok((Status::BadRequest, Json(report)));
+    }
+
+    report.accepted = ingest_resolved(state, &app, &events).await?;
+    Ok((Status::Ok, Json(report)))
+}
+
+/// Target app and event metadata for the text ingest routes.
+///
+/// Each value comes from a query parameter (`app_id`, `host`, `source`, `sourcetype`) or,
+/// failing that, the matching `X-Loglite-App-Id`, `X-Loglite-Host`, `X-Loglite-Source` or
+/// `X-Loglite-Sourcetype` header.
+pub struct TextTarget {
+    pub app_id: Option<String>,
+    pub host: String,
+    pub source: Option<String>,
+    pub sourcetype: Option<String>,
+    /// Parser for `/ingest/auto`, from `parser` or `X-Loglite-Parser`.
+    pub parser: Option<String>,
+    /// nginx `log_format` or Apache `LogFormat` string for access logs, from `log_format` or
+    /// `X-Loglite-Log-Format`.
+    pub log_format: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TextTarget {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let value = |param: &str, header: &str| {
+            req.query_value::<&str>(param)
+                .and_then(|v| v.ok())
+                .or_else(|| req.headers().get_one(header))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+        };
+        Outcome::Success(TextTarget {
+            app_id: value("app_id", "X-Loglite-App-Id"),
+            host: value("host", "X-Loglite-Host").unwrap_or_default(),
+            source: value("source", "X-Loglite-Source"),
+            sourcetype: value("sourcetype", "X-Loglite-Sourcetype"),
+            parser: value("parser", "X-Loglite-Parser"),
+            log_format: value("log_format", "X-Loglite-Log-Format"),
+        })
+    }
+}
+
+impl TextTarget {
+    /// App to ingest into; without one events go to `default`.
+    fn app(&self) -> &str {
+        self.app_id.as_deref().unwrap_or(DEFAULT_APP)
+    }
+
+    fn source(&self, default: &str) -> String {
+        self.source.as_deref().unwrap_or(default).to_string()
+    }
+
+    fn sourcetype(&self, default: &str) -> Option<String> {
+        Some(self.sourcetype.as_deref().unwrap_or(default).to_string())
+    }
+
+    /// The request's access log format; 422 when it does not compile.
+    fn access_log_format(&self) -> Result<Option<AccessLogFormat>, Status> {
+        self.log_format
+            .as_deref()
+            .map(AccessLogFormat::compile)
+            .transpose()
+            .map_err(|_| Status::UnprocessableEntity)
+    }
+}
+
+/// Read a text log body, decompressing it per `Content-Encoding`.
+///
+/// The compressed size is capped by the `string` limit, as for a plain `String` body.
+async fn read_text(
+    body: Data<'_>,
+    limits: &Limits,
+    encoding: &BodyEncoding,
+) -> Result<String, Status> {
+    let limit = limits.get("string").unwrap_or(8.kibibytes());
+    let raw = read_body(body, limit, encoding).await?;
+    String::from_utf8(raw).map_err(|_| Status::BadRequest)
 }
 
 /// Ingest events endpoint.
-#[post("/ingest", data = "<payload>")]
+///
+/// The body may be gzip, deflate or zstd compressed. Events may carry an `idempotency_key`,
+/// or the whole batch may be keyed with an `Idempotency-Key` header. Invalid events are
+/// reported by index; see [`IngestMode`] for how they affect the rest of the batch.
+#[post("/ingest", data = "<body>")]
 pub async fn ingest(
     state: &State<AppState>,
-    payload: Json<IngestRequest>,
-) -> Result<Json<IngestResponse>, Status> {
-    let accepted = ingest_events_for_app(state.inner(), &payload.app_id, &payload.events).await?;
+    mode: IngestMode,
+    batch_key: BatchKey,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let limit = limits.get("json").unwrap_or(1.mebibytes());
+    let raw = read_body(body, limit, &encoding).await?;
+    let payload: IngestRequest =
+        serde_json::from_slice(&raw).map_err(|_| Status::UnprocessableEntity)?;
+
+    let mut report = IngestResponse::default();
+    let mut events: Vec<IngestEvent> = Vec::with_capacity(payload.events.len());
+    for (i, value) in payload.events.into_iter().enumerate() {
+        match decode_event(value) {
+            Ok(mut event) => {
+                if event.idempotency_key.is_none() {
+                    event.idempotency_key = batch_key.key_for(i);
+                }
+                events.push(event);
+            }
+            Err(reason) => reject(&mut report, Some(i), reason),
+        }
+    }
 
-    Ok(Json(IngestResponse { accepted }))
+    store_batch(state.inner(), &payload.app_id, events, report, mode).await
 }
 
-/// Ingest nginx access logs endpoint.
-#[post("/ingest/nginx", data = "<body>")]
-pub async fn ingest_nginx(
-    state: &State<AppState>,
-    body: String,
-) -> Result<Json<IngestResponse>, Status> {
+/// Parse nginx or Apache access lines with `format`, or the default formats.
+fn nginx_events(
+    target: &TextTarget,
+    format: Option<&AccessLogFormat>,
+    body: &str,
+    source: &str,
+    sourcetype: &str,
+    report: &mut IngestResponse,
+) -> Vec<IngestEvent> {
     let mut events: Vec<IngestEvent> = Vec::new();
-    for line in body.lines() {
-        if let Some((msg, fields)) = parse_nginx_access_line(line) {
-            events.push(IngestEvent {
-                ts: default_ts(),
-                host: String::new(),
-                source: "nginx".to_string(),
-                sourcetype: Some("nginx_access".to_string()),
-                severity: None,
-                message: msg,
-                fields,
-            });
+    for (i, line) in body.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.len() > *MAX_EVENT_BYTES {
+            reject(
+                report,
+                Some(i),
+                format!(
+                    "line is {} bytes, over the {} byte limit",
+                    line.len(),
+                    *MAX_EVENT_BYTES
+                ),
+            );
+            continue;
+        }
+        match parse_nginx_access_line(line, format) {
+            Some(entry) => events.push(IngestEvent {
+                ts: entry.timestamp,
+                host: target.host.clone(),
+                source: target.source(source),
+                sourcetype: target.sourcetype(sourcetype),
+                severity: level_to_severity(&entry.level),
+                message: entry.message,
+                fields: entry.fields,
+                idempotency_key: None,
+            }),
+            None => reject(report, Some(i), "unparseable nginx access line".to_string()),
         }
     }
+    events
+}
 
-    let accepted = ingest_events_for_app(state.inner(), "default", &events).await?;
-    Ok(Json(IngestResponse { accepted }))
+/// Parse a multi-line application log body in `format`.
+fn app_log_events(
+    target: &TextTarget,
+    body: &str,
+    format: &LogFormat,
+    source: &str,
+    sourcetype: &str,
+    report: &mut IngestResponse,
+) -> Vec<IngestEvent> {
+    let lines = checked_lines(body, report);
+    merge_multiline_logs(lines, format)
+        .into_iter()
+        .map(|entry| IngestEvent {
+            ts: entry.timestamp,
+            host: target.host.clone(),
+            source: target.source(source),
+            sourcetype: target.sourcetype(sourcetype),
+            severity: level_to_severity(&entry.level),
+            message: entry.message,
+            fields: entry.fields,
+            idempotency_key: None,
+        })
+        .collect()
+}
+
+/// Shared tail of the text routes: key, then store per `mode`.
+async fn store_text(
+    state: &AppState,
+    target: &TextTarget,
+    batch_key: &BatchKey,
+    mut events: Vec<IngestEvent>,
+    report: IngestResponse,
+    mode: IngestMode,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    batch_key.apply(&mut events);
+    store_batch(state, target.app(), events, report, mode).await
+}
+
+/// Ingest nginx access logs endpoint.
+///
+/// Lines are parsed with the `log_format` query parameter or `X-Loglite-Log-Format` header,
+/// else `LOGLITE_NGINX_LOG_FORMAT`, else the `combined` and `common` formats; a format that
+/// does not compile is rejected with 422.
+#[post("/ingest/nginx", data = "<body>")]
+pub async fn ingest_nginx(
+    state: &State<AppState>,
+    target: TextTarget,
+    mode: IngestMode,
+    batch_key: BatchKey,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let format = target.access_log_format()?;
+    let body = read_text(body, limits, &encoding).await?;
+    let mut report = IngestResponse::default();
+    let events = nginx_events(
+        &target,
+        format.as_ref(),
+        &body,
+        "nginx",
+        "nginx_access",
+        &mut report,
+    );
+    store_text(state.inner(), &target, &batch_key, events, report, mode).await
 }
 
 /// Ingest Java application logs endpoint.
 #[post("/ingest/java", data = "<body>")]
 pub async fn ingest_java(
     state: &State<AppState>,
-    body: String,
-) -> Result<Json<IngestResponse>, Status> {
-    let lines: Vec<&str> = body.lines().collect();
-    let log_entries = merge_multiline_logs(lines, LogFormat::Java);
-
-    let mut events: Vec<IngestEvent> = Vec::new();
-    for entry in log_entries {
-        events.push(IngestEvent {
-            ts: entry.timestamp,
-            host: String::new(),
-            source: "java".to_string(),
-            sourcetype: Some("java_app".to_string()),
-            severity: level_to_severity(&entry.level),
-            message: entry.message,
-            fields: entry.fields,
-        });
-    }
+    target: TextTarget,
+    mode: IngestMode,
+    batch_key: BatchKey,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let body = read_text(body, limits, &encoding).await?;
+    let mut report = IngestResponse::default();
+    let events = app_log_events(
+        &target,
+        &body,
+        &LogFormat::Java,
+        "java",
+        "java_app",
+        &mut report,
+    );
+    store_text(state.inner(), &target, &batch_key, events, report, mode).await
+}
 
-    let accepted = ingest_events_for_app(state.inner(), "default", &events).await?;
-    Ok(Json(IngestResponse { accepted }))
+/// Ingest Python application logs endpoint; tracebacks are kept with the record they follow.
+#[post("/ingest/python", data = "<body>")]
+pub async fn ingest_python(
+    state: &State<AppState>,
+    target: TextTarget,
+    mode: IngestMode,
+    batch_key: BatchKey,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let body = read_text(body, limits, &encoding).await?;
+    let mut report = IngestResponse::default();
+    let events = app_log_events(
+        &target,
+        &body,
+        &LogFormat::Python,
+        "python",
+        "python_app",
+        &mut report,
+    );
+    store_text(state.inner(), &target, &batch_key, events, report, mode).await
 }
 
 /// Ingest Rust application logs endpoint.
 #[post("/ingest/rust", data = "<body>")]
 pub async fn ingest_rust(
     state: &State<AppState>,
-    body: String,
-) -> Result<Json<IngestResponse>, Status> {
-    let lines: Vec<&str> = body.lines().collect();
-    let log_entries = merge_multiline_logs(lines, LogFormat::Rust);
-
-    let mut events: Vec<IngestEvent> = Vec::new();
-    for entry in log_entries {
-        events.push(IngestEvent {
-            ts: entry.timestamp,
-            host: String::new(),
-            source: "rust".to_string(),
-            sourcetype: Some("rust_app".to_string()),
-            severity: level_to_severity(&entry.level),
-            message: entry.message,
-            fields: entry.fields,
-        });
-    }
-
-    let accepted = ingest_events_for_app(state.inner(), "default", &events).await?;
-    Ok(Json(IngestResponse { accepted }))
+    target: TextTarget,
+    mode: IngestMode,
+    batch_key: BatchKey,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let body = read_text(body, limits, &encoding).await?;
+    let mut report = IngestResponse::default();
+    let events = app_log_events(
+        &target,
+        &body,
+        &LogFormat::Rust,
+        "rust",
+        "rust_app",
+        &mut report,
+    );
+    store_text(state.inner(), &target, &batch_key, events, report, mode).await
 }
 
 /// Ingest Go application logs endpoint.
 #[post("/ingest/go", data = "<body>")]
 pub async fn ingest_go(
     state: &State<AppState>,
-    body: String,
-) -> Result<Json<IngestResponse>, Status> {
-    let lines: Vec<&str> = body.lines().collect();
-    let log_entries = merge_multiline_logs(lines, LogFormat::Go);
-
-    let mut events: Vec<IngestEvent> = Vec::new();
-    for entry in log_entries {
-        events.push(IngestEvent {
-            ts: entry.timestamp,
-            host: String::new(),
-            source: "go".to_string(),
-            sourcetype: Some("go_app".to_string()),
-            severity: level_to_severity(&entry.level),
-            message: entry.message,
-            fields: entry.fields,
-        });
-    }
-
-    let accepted = ingest_events_for_app(state.inner(), "default", &events).await?;
-    Ok(Json(IngestResponse { accepted }))
+    target: TextTarget,
+    mode: IngestMode,
+    batch_key: BatchKey,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let body = read_text(body, limits, &encoding).await?;
+    let mut report = IngestResponse::default();
+    let events = app_log_events(&target, &body, &LogFormat::Go, "go", "go_app", &mut report);
+    store_text(state.inner(), &target, &batch_key, events, report, mode).await
 }
 
 /// Ingest logs with automatic format detection.
+///
+/// Detection tries the built-in formats, then the user-defined parsers; a `parser` query
+/// parameter or `X-Loglite-Parser` header names the parser to use instead, and an unknown
+/// parser is rejected with 422. A `log_format` reads the body as access logs in that format.
 #[post("/ingest/auto", data = "<body>")]
 pub async fn ingest_auto(
     state: &State<AppState>,
-    body: String,
-) -> Result<Json<IngestResponse>, Status> {
+    target: TextTarget,
+    mode: IngestMode,
+    batch_key: BatchKey,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let body = read_text(body, limits, &encoding).await?;
     let lines: Vec<&str> = body.lines().collect();
 
-    // Detect format from sample
-    let format = detect_log_format(&lines);
+    // Use the named parser or access log format, or detect the format from a sample
+    let format = match &target.parser {
+        Some(name) => {
+            LogFormat::Custom(state.parsers.get(name).ok_or(Status::UnprocessableEntity)?)
+        }
+        None if target.log_format.is_some() => LogFormat::Nginx,
+        None => detect_log_format(&lines, &state.parsers.all()),
+    };
 
     if format == LogFormat::Unknown {
-        return Err(Status::BadRequest);
+        return Err(Status::BadRequest.into());
     }
 
-    let (source_name, sourcetype_name) = match format {
+    let (source_name, sourcetype_name) = match &format {
         LogFormat::Java => ("java", "java_app"),
         LogFormat::Rust => ("rust", "rust_app"),
         LogFormat::Go => ("go", "go_app"),
+        LogFormat::Python => ("python", "python_app"),
         LogFormat::Nginx => ("nginx", "nginx_access"),
+        LogFormat::Logfmt => ("logfmt", "logfmt"),
+        LogFormat::Custom(parser) => (parser.name.as_str(), parser.name.as_str()),
         LogFormat::Unknown => ("unknown", "unknown"),
     };
 
-    // Handle Nginx separately (doesn't use merge_multiline_logs)
-    if format == LogFormat::Nginx {
-        let mut events: Vec<IngestEvent> = Vec::new();
-        for line in lines {
-            if let Some((msg, fields)) = parse_nginx_access_line(line) {
-                events.push(IngestEvent {
-                    ts: default_ts(),
-                    host: String::new(),
-                    source: source_name.to_string(),
-                    sourcetype: Some(sourcetype_name.to_string()),
-                    severity: None,
-                    message: msg,
-                    fields,
-                });
-            }
-        }
-        let accepted = ingest_events_for_app(state.inner(), "default", &events).await?;
-        return Ok(Json(IngestResponse { accepted }));
-    }
-
-    // Parse with detected format
-    let log_entries = merge_multiline_logs(lines, format);
-
-    let mut events: Vec<IngestEvent> = Vec::new();
-    for entry in log_entries {
-        events.push(IngestEvent {
-            ts: entry.timestamp,
-            host: String::new(),
-            source: source_name.to_string(),
-            sourcetype: Some(sourcetype_name.to_string()),
-            severity: level_to_severity(&entry.level),
-            message: entry.message,
-            fields: entry.fields,
-        });
-    }
-
-    let accepted = ingest_events_for_app(state.inner(), "default", &events).await?;
-    Ok(Json(IngestResponse { accepted }))
+    let mut report = IngestResponse::default();
+    let events = if format == LogFormat::Nginx {
+        // Nginx lines are single-line and don't use merge_multiline_logs
+        let access_format = target.access_log_format()?;
+        nginx_events(
+            &target,
+            access_format.as_ref(),
+            &body,
+            source_name,
+            sourcetype_name,
+            &mut report,
+        )
+    } else {
+        app_log_events(
+            &target,
+            &body,
+            &format,
+            source_name,
+            sourcetype_name,
+            &mut report,
+        )
+    };
+    store_text(state.inner(), &target, &batch_key, events, report, mode).await
 }
@@ -0,0 +1,82 @@
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::State;
+use std::collections::HashMap;
+
+use crate::db::DEFAULT_APP;
+use crate::handlers::body::{read_body, BodyEncoding};
+use crate::handlers::ingest::IngestFailure;
+use crate::handlers::{ingest_resolved, resolve_app};
+use crate::loki::{decode_json_push, decode_protobuf_push};
+use crate::models::IngestEvent;
+use crate::state::AppState;
+
+/// Loki tenant id from the `X-Scope-OrgID` header.
+pub struct ScopeOrgId(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ScopeOrgId {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let org_id = req
+            .headers()
+            .get_one("X-Scope-OrgID")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        Outcome::Success(ScopeOrgId(org_id))
+    }
+}
+
+fn default_app_id() -> String {
+    std::env::var("LOGLITE_LOKI_APP_ID").unwrap_or_else(|_| DEFAULT_APP.to_string())
+}
+
+/// Loki-compatible push endpoint.
+///
+/// Accepts the snappy-compressed protobuf form used by Promtail and Grafana Agent, and the
+/// JSON form (optionally compressed). Streams are routed by their `app` label, then by
+/// the `X-Scope-OrgID` tenant, then to `LOGLITE_LOKI_APP_ID`; unknown apps follow the
+/// unknown-app policy. Every app is resolved before anything is stored, and entries are keyed
+/// on their content, so a re-sent push does not store them twice.
+#[post("/loki/api/v1/push", data = "<body>")]
+pub async fn loki_push(
+    state: &State<AppState>,
+    encoding: BodyEncoding,
+    org_id: ScopeOrgId,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<Status, IngestFailure> {
+    let limit = limits.get("loki").unwrap_or(16.mebibytes());
+    let raw = read_body(body, limit, &encoding).await?;
+
+    let streams = if encoding.json {
+        decode_json_push(&raw)
+    } else {
+        decode_protobuf_push(&raw)
+    }
+    .map_err(|_| Status::BadRequest)?;
+
+    let mut by_app: HashMap<String, Vec<IngestEvent>> = HashMap::new();
+    for stream in &streams {
+        let app_id = match stream.label("app").or(org_id.0.as_deref()) {
+            Some(name) => name.to_string(),
+            None => default_app_id(),
+        };
+        by_app
+            .entry(app_id)
+            .or_default()
+            .extend(stream.to_ingest_events());
+    }
+
+    let mut resolved = Vec::with_capacity(by_app.len());
+    for (app_id, events) in &by_app {
+        resolved.push((resolve_app(state.inner(), app_id).await?, events));
+    }
+    for (app, events) in &resolved {
+        ingest_resolved(state.inner(), app, events).await?;
+    }
+
+    Ok(Status::NoContent)
+}
@@ -1,14 +1,52 @@
 mod apps;
+mod body;
+mod elastic;
+mod filters;
+mod gelf;
 mod health;
+mod hec;
 mod ingest;
+mod loki;
+mod ndjson;
+mod otlp;
+mod parsers;
+mod pipelines;
+mod queue;
+mod quotas;
+mod redactions;
 mod search_handler;
 mod sources;
 mod ttl;
 
-pub use apps::{create_app, list_apps};
+pub use apps::{create_app, list_apps, resolve_app};
+pub use elastic::{elastic_bulk, elastic_bulk_index, elastic_info};
+pub use filters::{
+    create_filter_rule, delete_filter_rule, filter_stats, get_filter_rule, list_filter_rules,
+    update_filter_rule,
+};
+pub use gelf::ingest_gelf;
 pub use health::health as health_handler;
+pub use hec::{hec_collector, hec_event, hec_health, hec_raw};
 pub use ingest::{
-    ingest as ingest_handler, ingest_auto, ingest_go, ingest_java, ingest_nginx, ingest_rust,
+    ingest as ingest_handler, ingest_auto, ingest_events_for_app, ingest_go, ingest_java,
+    ingest_nginx, ingest_python, ingest_resolved, ingest_rust,
+};
+pub use loki::loki_push;
+pub use ndjson::ingest_ndjson;
+pub use otlp::otlp_logs;
+pub use parsers::{
+    create_parser, delete_parser, get_parser, list_grok_patterns, list_parsers, test_parser,
+    update_parser,
+};
+pub use pipelines::{
+    create_pipeline, delete_pipeline, dry_run_pipeline, get_pipeline, list_pipelines,
+    update_pipeline,
+};
+pub use queue::{queue_stats, replay_dead_letters};
+pub use quotas::{app_usage, delete_app_quota, get_app_quota, set_app_quota};
+pub use redactions::{
+    create_redaction_rule, delete_redaction_rule, get_redaction_rule, list_redaction_rules,
+    redaction_stats, update_redaction_rule,
 };
 pub use search_handler::search as search_handler_fn;
 pub use sources::{create_source, delete_source, get_source, list_sources, update_source};
@@ -0,0 +1,127 @@
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+
+use super::apps::resolve_app;
+use super::ingest::{
+    decode_event, ingest_resolved, reject, BatchKey, IngestFailure, MAX_EVENT_BYTES,
+};
+use crate::models::{IngestError, IngestEvent, IngestResponse};
+use crate::state::AppState;
+
+/// Number of parsed lines persisted per ingest call.
+const NDJSON_BATCH_SIZE: usize = 1000;
+
+/// Discard the rest of an oversize line, returning the number of bytes skipped.
+async fn skip_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<u64> {
+    let mut skipped = 0u64;
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            return Ok(skipped);
+        }
+        match buf.iter().position(|&b| b == b'\n') {
+            Some(pos) => {
+                reader.consume(pos + 1);
+                return Ok(skipped + pos as u64 + 1);
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+                skipped += len as u64;
+            }
+        }
+    }
+}
+
+/// Stream NDJSON events into an application, one `IngestEvent` per line.
+///
+/// The body is read incrementally and persisted in batches of `NDJSON_BATCH_SIZE`, so the
+/// request size is bounded only by the `ndjson` limit (16 GiB by default). Blank lines are
+/// skipped; invalid or oversize lines are rejected by zero-based line index while the rest
+/// are stored, as in `partial` mode. Batches already stored stay stored if the request later
+/// fails; send an `Idempotency-Key` header to make such a request safe to retry.
+#[post("/ingest/stream?<app_id>", data = "<body>")]
+pub async fn ingest_ndjson(
+    state: &State<AppState>,
+    app_id: &str,
+    batch_key: BatchKey,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(Status, Json<IngestResponse>), IngestFailure> {
+    let mut report = IngestResponse::default();
+    let app = match resolve_app(state.inner(), app_id).await {
+        Ok(app) => app,
+        Err(status) if status == Status::NotFound => {
+            report.errors.push(IngestError {
+                index: None,
+                reason: format!("unknown app: {}", app_id),
+            });
+            return Ok((Status::NotFound, Json(report)));
+        }
+        Err(status) => return Err(status.into()),
+    };
+
+    let limit = limits.get("ndjson").unwrap_or(16.gibibytes());
+    let max_line = *MAX_EVENT_BYTES as u64;
+    let mut reader = BufReader::new(body.open(limit));
+
+    let mut batch: Vec<IngestEvent> = Vec::with_capacity(NDJSON_BATCH_SIZE);
+    let mut line: Vec<u8> = Vec::new();
+    let mut read_bytes: u64 = 0;
+
+    for index in 0usize.. {
+        line.clear();
+        let n = (&mut reader)
+            .take(max_line + 1)
+            .read_until(b'\n', &mut line)
+            .await
+            .map_err(|_| Status::BadRequest)?;
+        if n == 0 {
+            break;
+        }
+        read_bytes += n as u64;
+
+        if line.len() as u64 > max_line && line.last() != Some(&b'\n') {
+            read_bytes += skip_line(&mut reader)
+                .await
+                .map_err(|_| Status::BadRequest)?;
+            reject(
+                &mut report,
+                Some(index),
+                format!("line is over the {} byte limit", max_line),
+            );
+            continue;
+        }
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            continue;
+        }
+
+        let decoded = serde_json::from_slice::<serde_json::Value>(&line)
+            .map_err(|e| format!("invalid JSON: {}", e))
+            .and_then(decode_event);
+        match decoded {
+            Ok(mut event) => {
+                if event.idempotency_key.is_none() {
+                    event.idempotency_key = batch_key.key_for(index);
+                }
+                batch.push(event);
+            }
+            Err(reason) => reject(&mut report, Some(index), reason),
+        }
+
+        if batch.len() >= NDJSON_BATCH_SIZE {
+            report.accepted += ingest_resolved(state.inner(), &app, &batch).await?;
+            batch.clear();
+        }
+    }
+
+    if read_bytes >= limit.as_u64() {
+        return Err(Status::PayloadTooLarge.into());
+    }
+
+    report.accepted += ingest_resolved(state.inner(), &app, &batch).await?;
+    Ok((Status::Ok, Json(report)))
+}
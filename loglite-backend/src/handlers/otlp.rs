@@ -0,0 +1,135 @@
+use prost::Message;
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::http::{ContentType, Status};
+use rocket::State;
+use std::collections::HashMap;
+
+use crate::db::DEFAULT_APP;
+use crate::handlers::body::{read_body, BodyEncoding};
+use crate::handlers::ingest::{ingest_resolved, IngestFailure};
+use crate::handlers::resolve_app;
+use crate::models::IngestEvent;
+use crate::otlp::{
+    decode_json_request, record_key, string_attribute, ExportLogsPartialSuccess,
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use crate::state::AppState;
+
+fn default_app_id() -> String {
+    std::env::var("LOGLITE_OTLP_APP_ID").unwrap_or_else(|_| DEFAULT_APP.to_string())
+}
+
+/// OTLP/HTTP logs receiver.
+///
+/// Accepts `ExportLogsServiceRequest` as protobuf or JSON, optionally compressed. Each
+/// resource is routed to `app_id` when given, otherwise to the app named by its
+/// `service.name` attribute, otherwise to `LOGLITE_OTLP_APP_ID`; unknown apps follow the
+/// unknown-app policy. Every app is resolved before anything is stored, and records are keyed
+/// on their content, so a retried export does not store them twice.
+/// Records without a body or attributes, and those of resources whose app is rejected, are
+/// reported through OTLP partial success while the rest are stored.
+#[post("/v1/logs?<app_id>", data = "<body>")]
+pub async fn otlp_logs(
+    state: &State<AppState>,
+    app_id: Option<String>,
+    encoding: BodyEncoding,
+    limits: &Limits,
+    body: Data<'_>,
+) -> Result<(ContentType, Vec<u8>), IngestFailure> {
+    let limit = limits.get("otlp").unwrap_or(16.mebibytes());
+    let raw = read_body(body, limit, &encoding).await?;
+
+    let request = if encoding.json {
+        decode_json_request(&raw).map_err(|_| Status::BadRequest)?
+    } else {
+        ExportLogsServiceRequest::decode(raw.as_slice()).map_err(|_| Status::BadRequest)?
+    };
+
+    let mut by_app: HashMap<String, Vec<IngestEvent>> = HashMap::new();
+    let mut rejected: i64 = 0;
+    let mut errors: Vec<String> = Vec::new();
+
+    for resource_logs in &request.resource_logs {
+        let resource = resource_logs
+            .resource
+            .as_ref()
+            .map(|r| r.attributes.as_slice())
+            .unwrap_or_default();
+
+        let target = match (&app_id, string_attribute(resource, "service.name")) {
+            (Some(app_id), _) => app_id.clone(),
+            (None, Some(service)) => service.to_string(),
+            (None, None) => default_app_id(),
+        };
+
+        let events = by_app.entry(target).or_default();
+        for scope_logs in &resource_logs.scope_logs {
+            let scope = scope_logs.scope.as_ref();
+            for (i, record) in scope_logs.log_records.iter().enumerate() {
+                match record.to_ingest_event(resource, scope) {
+                    Some(mut event) => {
+                        event.idempotency_key = Some(record_key(resource, scope, record, i));
+                        events.push(event);
+                    }
+                    None => rejected += 1,
+                }
+            }
+        }
+    }
+    if rejected > 0 {
+        errors.push("log records without body or attributes were rejected".to_string());
+    }
+
+    // OTLP clients retry on 503, so storage failures are reported as retryable, and an app
+    // over its quota as a 429, which they retry after `Retry-After`. Records of a rejected
+    // unknown app would be rejected again, so they go to partial success instead.
+    let mut resolved = Vec::with_capacity(by_app.len());
+    for (app_id, events) in &by_app {
+        match resolve_app(state.inner(), app_id).await {
+            Ok(app) => resolved.push((app, events)),
+            Err(status) if status == Status::NotFound => {
+                if !events.is_empty() {
+                    rejected += events.len() as i64;
+                    errors.push(format!("unknown app {}", app_id));
+                }
+            }
+            Err(_) => return Err(Status::ServiceUnavailable.into()),
+        }
+    }
+
+    for (app, events) in &resolved {
+        ingest_resolved(state.inner(), app, events)
+            .await
+            .map_err(|e| {
+                if e.status == Status::NotFound || e.status == Status::TooManyRequests {
+                    e
+                } else {
+                    Status::ServiceUnavailable.into()
+                }
+            })?;
+    }
+
+    let partial_success = (rejected > 0).then(|| ExportLogsPartialSuccess {
+        rejected_log_records: rejected,
+        error_message: errors.join("; "),
+    });
+
+    if encoding.json {
+        let body = match partial_success {
+            Some(p) => serde_json::json!({
+                "partialSuccess": {
+                    "rejectedLogRecords": p.rejected_log_records.to_string(),
+                    "errorMessage": p.error_message,
+                }
+            }),
+            None => serde_json::json!({}),
+        };
+        Ok((ContentType::JSON, body.to_string().into_bytes()))
+    } else {
+        let response = ExportLogsServiceResponse { partial_success };
+        Ok((
+            ContentType::new("application", "x-protobuf"),
+            response.encode_to_vec(),
+        ))
+    }
+}
@@ -0,0 +1,229 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+
+use crate::entities::prelude::*;
+use crate::grok::library_names;
+use crate::models::{
+    CreateParserRequest, ParsedLine, ParserInfo, ParserTestRequest, ParserTestResult,
+    UpdateParserRequest,
+};
+use crate::parser::{CaptureMapping, CompiledParser};
+use crate::state::AppState;
+use crate::utils::level_to_severity;
+
+fn default_ts() -> DateTimeWithTimeZone {
+    let utc: DateTime<Utc> = Utc::now();
+    utc.with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+fn to_info(model: crate::entities::parsers::Model) -> ParserInfo {
+    ParserInfo {
+        id: model.id,
+        name: model.name,
+        pattern: model.pattern,
+        timestamp_format: model.timestamp_format,
+        mapping: model.mapping,
+        created_at: model.created_at,
+    }
+}
+
+async fn reload(state: &AppState) -> Result<(), Status> {
+    state.parsers.reload(state.db.as_ref()).await.map_err(|e| {
+        tracing::error!("Failed to reload parsers: {}", e);
+        Status::InternalServerError
+    })
+}
+
+async fn find_by_name(
+    state: &AppState,
+    name: &str,
+) -> Result<crate::entities::parsers::Model, Status> {
+    Parser::find()
+        .filter(crate::entities::parsers::Column::Name.eq(name))
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)
+}
+
+/// Create a parser.
+///
+/// An empty name or a pattern that does not compile is rejected with 422, and a name already
+/// in use with 409.
+#[post("/parsers", data = "<payload>")]
+pub async fn create_parser(
+    state: &State<AppState>,
+    payload: Json<CreateParserRequest>,
+) -> Result<Json<ParserInfo>, Status> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(Status::UnprocessableEntity);
+    }
+    CompiledParser::compile(
+        name,
+        &payload.pattern,
+        payload.timestamp_format.as_deref(),
+        payload.mapping.clone(),
+    )
+    .map_err(|_| Status::UnprocessableEntity)?;
+    match find_by_name(state, name).await {
+        Err(status) if status == Status::NotFound => {}
+        Ok(_) => return Err(Status::Conflict),
+        Err(status) => return Err(status),
+    }
+
+    let model = crate::entities::parsers::ActiveModel {
+        id: Set(0), // Auto-increment
+        name: Set(name.to_string()),
+        pattern: Set(payload.pattern.clone()),
+        timestamp_format: Set(payload.timestamp_format.clone()),
+        mapping: Set(
+            serde_json::to_value(&payload.mapping).map_err(|_| Status::InternalServerError)?
+        ),
+        created_at: Set(default_ts()),
+    }
+    .insert(state.db.as_ref())
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(model)))
+}
+
+/// List all parsers.
+#[get("/parsers")]
+pub async fn list_parsers(state: &State<AppState>) -> Result<Json<Vec<ParserInfo>>, Status> {
+    let parsers = Parser::find()
+        .order_by_asc(crate::entities::parsers::Column::Id)
+        .all(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(parsers.into_iter().map(to_info).collect()))
+}
+
+/// Names of the built-in grok patterns.
+#[get("/parsers/patterns")]
+pub fn list_grok_patterns() -> Json<Vec<&'static str>> {
+    Json(library_names())
+}
+
+/// Parse sample lines with a stored parser or an unsaved pattern, without storing anything.
+#[post("/parsers/test", data = "<payload>")]
+pub async fn test_parser(
+    state: &State<AppState>,
+    payload: Json<ParserTestRequest>,
+) -> Result<Json<Vec<ParserTestResult>>, Status> {
+    let parser = match (&payload.pattern, &payload.name) {
+        (Some(pattern), _) => std::sync::Arc::new(
+            CompiledParser::compile(
+                "test",
+                pattern,
+                payload.timestamp_format.as_deref(),
+                payload.mapping.clone(),
+            )
+            .map_err(|_| Status::UnprocessableEntity)?,
+        ),
+        (None, Some(name)) => state.parsers.get(name).ok_or(Status::NotFound)?,
+        (None, None) => return Err(Status::UnprocessableEntity),
+    };
+
+    let results = payload
+        .lines
+        .iter()
+        .map(|line| ParserTestResult {
+            line: line.clone(),
+            entry: parser.parse_line(line).map(|entry| ParsedLine {
+                ts: entry.timestamp,
+                severity: level_to_severity(&entry.level),
+                level: entry.level,
+                message: entry.message,
+                fields: entry.fields,
+            }),
+        })
+        .collect();
+    Ok(Json(results))
+}
+
+/// Get a single parser by name.
+#[get("/parsers/<name>")]
+pub async fn get_parser(state: &State<AppState>, name: &str) -> Result<Json<ParserInfo>, Status> {
+    Ok(Json(to_info(find_by_name(state, name).await?)))
+}
+
+/// Update a parser.
+#[put("/parsers/<name>", data = "<payload>")]
+pub async fn update_parser(
+    state: &State<AppState>,
+    name: &str,
+    payload: Json<UpdateParserRequest>,
+) -> Result<Json<ParserInfo>, Status> {
+    let parser = find_by_name(state, name).await?;
+
+    let pattern = payload.pattern.as_ref().unwrap_or(&parser.pattern);
+    let timestamp_format = payload
+        .timestamp_format
+        .as_ref()
+        .or(parser.timestamp_format.as_ref());
+    let mapping = match &payload.mapping {
+        Some(mapping) => mapping.clone(),
+        None => serde_json::from_value::<CaptureMapping>(parser.mapping.clone())
+            .map_err(|_| Status::InternalServerError)?,
+    };
+    CompiledParser::compile(
+        name,
+        pattern,
+        timestamp_format.map(String::as_str),
+        mapping.clone(),
+    )
+    .map_err(|_| Status::UnprocessableEntity)?;
+
+    let mut active: crate::entities::parsers::ActiveModel = parser.into();
+
+    if let Some(pattern) = &payload.pattern {
+        active.pattern = Set(pattern.clone());
+    }
+    if payload.timestamp_format.is_some() {
+        active.timestamp_format = Set(payload.timestamp_format.clone());
+    }
+    if payload.mapping.is_some() {
+        active.mapping =
+            Set(serde_json::to_value(&mapping).map_err(|_| Status::InternalServerError)?);
+    }
+
+    let updated = active
+        .update(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(updated)))
+}
+
+/// Delete a parser; 409 while a source still uses it.
+#[delete("/parsers/<name>")]
+pub async fn delete_parser(state: &State<AppState>, name: &str) -> Result<Status, Status> {
+    let parser = find_by_name(state, name).await?;
+
+    let in_use = AppSource::find()
+        .filter(crate::entities::app_sources::Column::Parser.eq(name))
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    if in_use.is_some() {
+        return Err(Status::Conflict);
+    }
+
+    let active: crate::entities::parsers::ActiveModel = parser.into();
+    active
+        .delete(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Status::NoContent)
+}
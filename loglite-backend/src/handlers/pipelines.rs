@@ -0,0 +1,194 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+
+use super::apps::require_app;
+use crate::entities::prelude::*;
+use crate::models::{
+    CreatePipelineRequest, IngestEvent, PipelineDryRunRequest, PipelineInfo, UpdatePipelineRequest,
+};
+use crate::pipeline::{process_events, CompiledPipeline, Processor};
+use crate::state::AppState;
+
+fn default_ts() -> DateTimeWithTimeZone {
+    let utc: DateTime<Utc> = Utc::now();
+    utc.with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+fn to_info(model: crate::entities::pipelines::Model) -> PipelineInfo {
+    PipelineInfo {
+        id: model.id,
+        app_id: model.app_id,
+        source_id: model.source_id,
+        name: model.name,
+        processors: model.processors,
+        enabled: model.enabled,
+        created_at: model.created_at,
+    }
+}
+
+/// Compile `processors` to validate them, returning them as stored JSON.
+fn validate(processors: &[Processor]) -> Result<serde_json::Value, Status> {
+    CompiledPipeline::compile(processors.to_vec()).map_err(|_| Status::UnprocessableEntity)?;
+    serde_json::to_value(processors).map_err(|_| Status::InternalServerError)
+}
+
+async fn reload(state: &AppState) -> Result<(), Status> {
+    state
+        .pipelines
+        .reload(state.db.as_ref())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to reload pipelines: {}", e);
+            Status::InternalServerError
+        })
+}
+
+/// Create a pipeline for an application, or for one of its sources.
+///
+/// Processors are validated up front; an invalid regex is rejected with 422.
+#[post("/pipelines", data = "<payload>")]
+pub async fn create_pipeline(
+    state: &State<AppState>,
+    payload: Json<CreatePipelineRequest>,
+) -> Result<Json<PipelineInfo>, Status> {
+    require_app(state, &payload.app_id).await?;
+    if let Some(source_id) = payload.source_id {
+        let source = AppSource::find_by_id(source_id)
+            .one(state.db.as_ref())
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+        if source.is_none_or(|s| s.app_id != payload.app_id) {
+            return Err(Status::UnprocessableEntity);
+        }
+    }
+    let processors = validate(&payload.processors)?;
+
+    let model = crate::entities::pipelines::ActiveModel {
+        id: Set(0), // Auto-increment
+        app_id: Set(payload.app_id.clone()),
+        source_id: Set(payload.source_id),
+        name: Set(payload.name.clone()),
+        processors: Set(processors),
+        enabled: Set(payload.enabled.unwrap_or(true)),
+        created_at: Set(default_ts()),
+    }
+    .insert(state.db.as_ref())
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(model)))
+}
+
+/// List all pipelines, optionally for one application.
+#[get("/pipelines?<app_id>")]
+pub async fn list_pipelines(
+    state: &State<AppState>,
+    app_id: Option<String>,
+) -> Result<Json<Vec<PipelineInfo>>, Status> {
+    let mut query = Pipeline::find();
+
+    if let Some(app_id) = app_id {
+        query = query.filter(crate::entities::pipelines::Column::AppId.eq(app_id));
+    }
+
+    let pipelines = query
+        .order_by_asc(crate::entities::pipelines::Column::Id)
+        .all(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(pipelines.into_iter().map(to_info).collect()))
+}
+
+/// Get a single pipeline by ID.
+#[get("/pipelines/<id>")]
+pub async fn get_pipeline(state: &State<AppState>, id: i64) -> Result<Json<PipelineInfo>, Status> {
+    let pipeline = Pipeline::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(to_info(pipeline)))
+}
+
+/// Update a pipeline.
+#[put("/pipelines/<id>", data = "<payload>")]
+pub async fn update_pipeline(
+    state: &State<AppState>,
+    id: i64,
+    payload: Json<UpdatePipelineRequest>,
+) -> Result<Json<PipelineInfo>, Status> {
+    let pipeline = Pipeline::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    let mut active: crate::entities::pipelines::ActiveModel = pipeline.into();
+
+    if let Some(name) = &payload.name {
+        active.name = Set(name.clone());
+    }
+    if let Some(processors) = &payload.processors {
+        active.processors = Set(validate(processors)?);
+    }
+    if let Some(enabled) = payload.enabled {
+        active.enabled = Set(enabled);
+    }
+
+    let updated = active
+        .update(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(updated)))
+}
+
+/// Delete a pipeline.
+#[delete("/pipelines/<id>")]
+pub async fn delete_pipeline(state: &State<AppState>, id: i64) -> Result<Status, Status> {
+    let pipeline = Pipeline::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    let active: crate::entities::pipelines::ActiveModel = pipeline.into();
+    active
+        .delete(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Status::NoContent)
+}
+
+/// Run sample events through a pipeline without storing anything.
+///
+/// Uses the processors in the request when given, otherwise the enabled pipelines of
+/// `app_id` (and `source_id`) exactly as ingestion would apply them.
+#[post("/pipelines/dry-run", data = "<payload>")]
+pub async fn dry_run_pipeline(
+    state: &State<AppState>,
+    payload: Json<PipelineDryRunRequest>,
+) -> Result<Json<Vec<IngestEvent>>, Status> {
+    let payload = payload.into_inner();
+    let pipelines = match (payload.processors, &payload.app_id) {
+        (Some(processors), _) => vec![std::sync::Arc::new(
+            CompiledPipeline::compile(processors).map_err(|_| Status::UnprocessableEntity)?,
+        )],
+        (None, Some(app_id)) => state.pipelines.for_source(app_id, payload.source_id),
+        (None, None) => return Err(Status::BadRequest),
+    };
+
+    Ok(Json(
+        process_events(&pipelines, &payload.events).unwrap_or(payload.events),
+    ))
+}
@@ -0,0 +1,39 @@
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+
+use crate::models::{WalReplay, WalStats};
+use crate::state::AppState;
+
+/// Ingestion queue depth.
+#[get("/ingest/queue")]
+pub async fn queue_stats(state: &State<AppState>) -> Json<WalStats> {
+    Json(match &state.wal {
+        Some(wal) => wal.stats(),
+        None => WalStats {
+            enabled: false,
+            queued_records: 0,
+            queued_events: 0,
+            queued_bytes: 0,
+            max_bytes: 0,
+            drained_events: 0,
+            dead_lettered_events: 0,
+        },
+    })
+}
+
+/// Re-queue the batches in the WAL dead-letter directory for another drain attempt.
+///
+/// Returns 404 when the WAL is disabled.
+#[post("/ingest/queue/replay")]
+pub async fn replay_dead_letters(state: &State<AppState>) -> Result<Json<WalReplay>, Status> {
+    let wal = state.wal.clone().ok_or(Status::NotFound)?;
+    let replay = tokio::task::spawn_blocking(move || wal.replay_dead_letters())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .map_err(|e| {
+            tracing::error!("Failed to replay dead-lettered WAL batches: {}", e);
+            Status::InternalServerError
+        })?;
+    Ok(Json(replay))
+}
@@ -0,0 +1,121 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+
+use super::apps::require_app;
+use crate::entities::prelude::*;
+use crate::models::{AppQuotaInfo, AppUsage, QuotaLimits};
+use crate::state::AppState;
+
+fn default_ts() -> DateTimeWithTimeZone {
+    let utc: DateTime<Utc> = Utc::now();
+    utc.with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+fn to_info(model: crate::entities::app_quotas::Model) -> AppQuotaInfo {
+    AppQuotaInfo {
+        limits: QuotaLimits::from(&model),
+        app_id: model.app_id,
+        updated_at: model.updated_at,
+    }
+}
+
+async fn reload(state: &AppState) -> Result<(), Status> {
+    state.quotas.reload(state.db.as_ref()).await.map_err(|e| {
+        tracing::error!("Failed to reload app quotas: {}", e);
+        Status::InternalServerError
+    })
+}
+
+/// Set the ingestion limits of an application, replacing any previous ones.
+///
+/// Omitted limits are unlimited; a zero or out-of-range limit is rejected with 422.
+#[put("/apps/<app_id>/quota", data = "<payload>")]
+pub async fn set_app_quota(
+    state: &State<AppState>,
+    app_id: &str,
+    payload: Json<QuotaLimits>,
+) -> Result<Json<AppQuotaInfo>, Status> {
+    require_app(state, app_id).await?;
+    let limit = |v: Option<u64>| -> Result<Option<i64>, Status> {
+        v.map(|v| match i64::try_from(v) {
+            Ok(v) if v > 0 => Ok(v),
+            _ => Err(Status::UnprocessableEntity),
+        })
+        .transpose()
+    };
+
+    let quota = crate::entities::app_quotas::ActiveModel {
+        app_id: Set(app_id.to_string()),
+        events_per_sec: Set(limit(payload.events_per_sec)?),
+        bytes_per_sec: Set(limit(payload.bytes_per_sec)?),
+        daily_events: Set(limit(payload.daily_events)?),
+        daily_bytes: Set(limit(payload.daily_bytes)?),
+        updated_at: Set(default_ts()),
+    };
+    let existing = AppQuota::find_by_id(app_id.to_string())
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    let model = match existing {
+        Some(_) => quota.update(state.db.as_ref()).await,
+        None => quota.insert(state.db.as_ref()).await,
+    }
+    .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(model)))
+}
+
+/// Get the ingestion limits of an application; 404 when it has none.
+#[get("/apps/<app_id>/quota")]
+pub async fn get_app_quota(
+    state: &State<AppState>,
+    app_id: &str,
+) -> Result<Json<AppQuotaInfo>, Status> {
+    let quota = AppQuota::find_by_id(app_id.to_string())
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(to_info(quota)))
+}
+
+/// Remove the ingestion limits of an application.
+#[delete("/apps/<app_id>/quota")]
+pub async fn delete_app_quota(state: &State<AppState>, app_id: &str) -> Result<Status, Status> {
+    let quota = AppQuota::find_by_id(app_id.to_string())
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    let active: crate::entities::app_quotas::ActiveModel = quota.into();
+    active
+        .delete(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Status::NoContent)
+}
+
+/// Current ingestion of an application against its limits.
+#[get("/apps/<app_id>/usage")]
+pub async fn app_usage(state: &State<AppState>, app_id: &str) -> Result<Json<AppUsage>, Status> {
+    require_app(state, app_id).await?;
+    let usage = state
+        .quotas
+        .usage(state.db.as_ref(), app_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to read usage of app {}: {}", app_id, e);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(usage))
+}
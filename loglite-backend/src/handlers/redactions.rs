@@ -0,0 +1,186 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+
+use super::apps::require_app;
+use crate::entities::prelude::*;
+use crate::models::{
+    CreateRedactionRuleRequest, RedactionRuleInfo, RedactionStats, UpdateRedactionRuleRequest,
+};
+use crate::redaction::{CompiledRule, Detector, RedactAction};
+use crate::state::AppState;
+
+fn default_ts() -> DateTimeWithTimeZone {
+    let utc: DateTime<Utc> = Utc::now();
+    utc.with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+fn to_info(model: crate::entities::redaction_rules::Model) -> RedactionRuleInfo {
+    RedactionRuleInfo {
+        id: model.id,
+        app_id: model.app_id,
+        name: model.name,
+        detector: model.detector,
+        pattern: model.pattern,
+        action: model.action,
+        enabled: model.enabled,
+        created_at: model.created_at,
+    }
+}
+
+async fn reload(state: &AppState) -> Result<(), Status> {
+    state
+        .redaction
+        .reload(state.db.as_ref())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to reload redaction rules: {}", e);
+            Status::InternalServerError
+        })
+}
+
+/// Create a redaction rule for an application.
+///
+/// A `regex` rule without a valid pattern is rejected with 422.
+#[post("/redactions", data = "<payload>")]
+pub async fn create_redaction_rule(
+    state: &State<AppState>,
+    payload: Json<CreateRedactionRuleRequest>,
+) -> Result<Json<RedactionRuleInfo>, Status> {
+    require_app(state, &payload.app_id).await?;
+    CompiledRule::compile(payload.detector, payload.pattern.as_deref(), payload.action)
+        .map_err(|_| Status::UnprocessableEntity)?;
+
+    let model = crate::entities::redaction_rules::ActiveModel {
+        id: Set(0), // Auto-increment
+        app_id: Set(payload.app_id.clone()),
+        name: Set(payload.name.clone()),
+        detector: Set(payload.detector.as_str().to_string()),
+        pattern: Set(payload.pattern.clone()),
+        action: Set(payload.action.as_str().to_string()),
+        enabled: Set(payload.enabled.unwrap_or(true)),
+        created_at: Set(default_ts()),
+    }
+    .insert(state.db.as_ref())
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(model)))
+}
+
+/// List all redaction rules, optionally for one application.
+#[get("/redactions?<app_id>")]
+pub async fn list_redaction_rules(
+    state: &State<AppState>,
+    app_id: Option<String>,
+) -> Result<Json<Vec<RedactionRuleInfo>>, Status> {
+    let mut query = RedactionRule::find();
+
+    if let Some(app_id) = app_id {
+        query = query.filter(crate::entities::redaction_rules::Column::AppId.eq(app_id));
+    }
+
+    let rules = query
+        .order_by_asc(crate::entities::redaction_rules::Column::Id)
+        .all(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(rules.into_iter().map(to_info).collect()))
+}
+
+/// Redaction counts since startup, in total and per rule.
+#[get("/redactions/stats")]
+pub async fn redaction_stats(state: &State<AppState>) -> Result<Json<RedactionStats>, Status> {
+    let rules = RedactionRule::find()
+        .order_by_asc(crate::entities::redaction_rules::Column::Id)
+        .all(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(state.redaction.stats(rules)))
+}
+
+/// Get a single redaction rule by ID.
+#[get("/redactions/<id>")]
+pub async fn get_redaction_rule(
+    state: &State<AppState>,
+    id: i64,
+) -> Result<Json<RedactionRuleInfo>, Status> {
+    let rule = RedactionRule::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(to_info(rule)))
+}
+
+/// Update a redaction rule.
+#[put("/redactions/<id>", data = "<payload>")]
+pub async fn update_redaction_rule(
+    state: &State<AppState>,
+    id: i64,
+    payload: Json<UpdateRedactionRuleRequest>,
+) -> Result<Json<RedactionRuleInfo>, Status> {
+    let rule = RedactionRule::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    let detector = Detector::parse(&rule.detector).ok_or(Status::InternalServerError)?;
+    let action = match payload.action {
+        Some(action) => action,
+        None => RedactAction::parse(&rule.action).ok_or(Status::InternalServerError)?,
+    };
+    let pattern = payload.pattern.clone().or_else(|| rule.pattern.clone());
+    CompiledRule::compile(detector, pattern.as_deref(), action)
+        .map_err(|_| Status::UnprocessableEntity)?;
+
+    let mut active: crate::entities::redaction_rules::ActiveModel = rule.into();
+
+    if let Some(name) = &payload.name {
+        active.name = Set(name.clone());
+    }
+    if payload.pattern.is_some() {
+        active.pattern = Set(pattern);
+    }
+    if let Some(action) = payload.action {
+        active.action = Set(action.as_str().to_string());
+    }
+    if let Some(enabled) = payload.enabled {
+        active.enabled = Set(enabled);
+    }
+
+    let updated = active
+        .update(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Json(to_info(updated)))
+}
+
+/// Delete a redaction rule.
+#[delete("/redactions/<id>")]
+pub async fn delete_redaction_rule(state: &State<AppState>, id: i64) -> Result<Status, Status> {
+    let rule = RedactionRule::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    let active: crate::entities::redaction_rules::ActiveModel = rule.into();
+    active
+        .delete(state.db.as_ref())
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    reload(state).await?;
+    Ok(Status::NoContent)
+}
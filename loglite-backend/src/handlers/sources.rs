@@ -14,12 +14,23 @@ fn default_ts() -> DateTimeWithTimeZone {
     utc.with_timezone(&FixedOffset::east_opt(0).unwrap())
 }
 
+/// Check that a referenced parser exists.
+fn check_parser(state: &AppState, parser: Option<&str>) -> Result<(), Status> {
+    match parser {
+        Some(name) if state.parsers.get(name).is_none() => Err(Status::UnprocessableEntity),
+        _ => Ok(()),
+    }
+}
+
 /// Create a log source for an application.
+///
+/// A `parser` that does not exist is rejected with 422.
 #[post("/sources", data = "<payload>")]
 pub async fn create_source(
     state: &State<AppState>,
     payload: Json<CreateSourceRequest>,
 ) -> Result<Json<SourceInfo>, Status> {
+    check_parser(state, payload.parser.as_deref())?;
     let created_at = default_ts();
 
     let model = crate::entities::app_sources::ActiveModel {
@@ -31,6 +42,7 @@ pub async fn create_source(
         encoding: Set(payload.encoding.clone().unwrap_or_else(|| "utf-8".to_string())),
         include_glob: Set(payload.include_glob.clone()),
         exclude_glob: Set(payload.exclude_glob.clone()),
+        parser: Set(payload.parser.clone()),
         enabled: Set(payload.enabled.unwrap_or(true)),
         created_at: Set(created_at),
     }
@@ -47,6 +59,7 @@ pub async fn create_source(
         encoding: model.encoding,
         include_glob: model.include_glob,
         exclude_glob: model.exclude_glob,
+        parser: model.parser,
         enabled: model.enabled,
         created_at: model.created_at,
     }))
@@ -82,6 +95,7 @@ pub async fn list_sources(
                 encoding: s.encoding,
                 include_glob: s.include_glob,
                 exclude_glob: s.exclude_glob,
+                parser: s.parser,
                 enabled: s.enabled,
                 created_at: s.created_at,
             })
@@ -107,6 +121,7 @@ pub async fn get_source(state: &State<AppState>, id: i64) -> Result<Json<SourceI
         encoding: source.encoding,
         include_glob: source.include_glob,
         exclude_glob: source.exclude_glob,
+        parser: source.parser,
         enabled: source.enabled,
         created_at: source.created_at,
     }))
@@ -142,6 +157,11 @@ pub async fn update_source(
     if let Some(exclude_glob) = &payload.exclude_glob {
         active.exclude_glob = Set(Some(exclude_glob.clone()));
     }
+    if let Some(parser) = &payload.parser {
+        let parser = Some(parser.clone()).filter(|p| !p.is_empty());
+        check_parser(state, parser.as_deref())?;
+        active.parser = Set(parser);
+    }
     if let Some(enabled) = payload.enabled {
         active.enabled = Set(enabled);
     }
@@ -160,6 +180,7 @@ pub async fn update_source(
         encoding: updated.encoding,
         include_glob: updated.include_glob,
         exclude_glob: updated.exclude_glob,
+        parser: updated.parser,
         enabled: updated.enabled,
         created_at: updated.created_at,
     }))
@@ -7,10 +7,12 @@ use tokio::time::{sleep, Duration};
 
 use crate::entities::prelude::*;
 use crate::state::AppState;
+use crate::store::DEDUP_WINDOW;
 
 /// Background task for TTL-based log cleanup.
 ///
-/// Periodically deletes expired events from both PostgreSQL and Tantivy index.
+/// Periodically deletes expired events from both PostgreSQL and Tantivy index, along with
+/// idempotency keys older than the dedup window.
 pub async fn ttl_cleanup_loop(state: Arc<AppState>) {
     let retention_days: i64 = std::env::var("LOGLITE_RETENTION_DAYS")
         .ok()
@@ -23,45 +25,63 @@ pub async fn ttl_cleanup_loop(state: Arc<AppState>) {
 
     loop {
         if retention_days > 0 {
-            let cutoff = Utc::now() - chrono::Duration::days(retention_days);
-            let cutoff = cutoff.with_timezone(&FixedOffset::east_opt(0).unwrap());
+            expire_events(&state, retention_days).await;
+        }
 
-            let expired: Vec<i64> = match Event::find()
-                .select_only()
-                .column(crate::entities::events::Column::Id)
-                .filter(crate::entities::events::Column::Ts.lt(cutoff))
-                .limit(10_000)
-                .into_tuple()
-                .all(state.db.as_ref())
-                .await
-            {
-                Ok(v) => v,
-                Err(_) => {
-                    sleep(Duration::from_secs(interval_seconds)).await;
-                    continue;
-                }
-            };
+        // Idempotency keys are only needed for the dedup window; they are expired even when
+        // the event cleanup above failed.
+        expire_keys(&state).await;
 
-            if !expired.is_empty() {
-                let _ = Event::delete_many()
-                    .filter(crate::entities::events::Column::Id.is_in(expired.clone()))
-                    .exec(state.db.as_ref())
-                    .await;
+        sleep(Duration::from_secs(interval_seconds)).await;
+    }
+}
 
-                {
-                    let mut writer = state.search.writer.lock();
-                    for id in expired {
-                        let term = Term::from_field_i64(state.search.field_event_id, id);
-                        let q = TermQuery::new(term, IndexRecordOption::Basic);
-                        let _ = writer.delete_query(Box::new(q));
-                    }
-                    let _ = writer.commit();
-                }
+/// Delete events older than the retention period from PostgreSQL and the Tantivy index.
+async fn expire_events(state: &AppState, retention_days: i64) {
+    let cutoff = Utc::now() - chrono::Duration::days(retention_days);
+    let cutoff = cutoff.with_timezone(&FixedOffset::east_opt(0).unwrap());
 
-                let _ = state.search.reader.reload();
-            }
-        }
+    let expired: Vec<i64> = match Event::find()
+        .select_only()
+        .column(crate::entities::events::Column::Id)
+        .filter(crate::entities::events::Column::Ts.lt(cutoff))
+        .limit(10_000)
+        .into_tuple()
+        .all(state.db.as_ref())
+        .await
+    {
+        Ok(v) => v,
+        Err(_) => return,
+    };
 
-        sleep(Duration::from_secs(interval_seconds)).await;
+    if !expired.is_empty() {
+        let _ = Event::delete_many()
+            .filter(crate::entities::events::Column::Id.is_in(expired.clone()))
+            .exec(state.db.as_ref())
+            .await;
+
+        // The writer lock waits for any running group commit, so it is taken off the runtime.
+        let search = state.search.clone();
+        let _ = tokio::task::spawn_blocking(move || {
+            let mut writer = search.writer.lock();
+            for id in expired {
+                let term = Term::from_field_i64(search.field_event_id, id);
+                let q = TermQuery::new(term, IndexRecordOption::Basic);
+                let _ = writer.delete_query(Box::new(q));
+            }
+            let _ = writer.commit();
+            drop(writer);
+            let _ = search.reader.reload();
+        })
+        .await;
     }
 }
+
+/// Delete idempotency keys older than the dedup window.
+async fn expire_keys(state: &AppState) {
+    let key_cutoff = (Utc::now() - *DEDUP_WINDOW).with_timezone(&FixedOffset::east_opt(0).unwrap());
+    let _ = IngestKey::delete_many()
+        .filter(crate::entities::ingest_keys::Column::CreatedAt.lt(key_cutoff))
+        .exec(state.db.as_ref())
+        .await;
+}
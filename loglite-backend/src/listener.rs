@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::handlers::ingest_events_for_app;
+use crate::models::IngestEvent;
+use crate::state::AppState;
+
+/// Maximum number of events flushed into a single ingest call.
+const FLUSH_BATCH_SIZE: usize = 500;
+
+/// Event received by a network listener, tagged with the app it routes to.
+pub type RoutedEvent = (String, IngestEvent);
+
+/// Parse a comma-separated port list such as `5514,6514`, skipping invalid entries.
+pub fn parse_port_list(s: &str) -> Vec<u16> {
+    s.split(',')
+        .filter_map(|p| p.trim().parse::<u16>().ok())
+        .collect()
+}
+
+/// Buffer routed events and flush them per app on a size/time budget.
+///
+/// Runs until every sender is dropped; `input` names the listener in error logs.
+pub async fn flush_loop(
+    state: Arc<AppState>,
+    mut rx: mpsc::Receiver<RoutedEvent>,
+    flush_interval: Duration,
+    input: &'static str,
+) {
+    let mut pending: HashMap<String, Vec<IngestEvent>> = HashMap::new();
+    let mut pending_count = 0usize;
+    let mut ticker = tokio::time::interval(flush_interval);
+
+    loop {
+        tokio::select! {
+            received = rx.recv() => {
+                match received {
+                    Some((app_id, event)) => {
+                        pending.entry(app_id).or_default().push(event);
+                        pending_count += 1;
+                        if pending_count < FLUSH_BATCH_SIZE {
+                            continue;
+                        }
+                    }
+                    None => {
+                        flush_pending(&state, &mut pending, input).await;
+                        return;
+                    }
+                }
+            }
+            _ = ticker.tick() => {}
+        }
+
+        flush_pending(&state, &mut pending, input).await;
+        pending_count = 0;
+    }
+}
+
+async fn flush_pending(
+    state: &AppState,
+    pending: &mut HashMap<String, Vec<IngestEvent>>,
+    input: &str,
+) {
+    for (app_id, events) in pending.drain() {
+        if let Err(status) = ingest_events_for_app(state, &app_id, &events).await {
+            tracing::error!(
+                "Failed to ingest {} {} events for app {}: {}",
+                events.len(),
+                input,
+                app_id,
+                status
+            );
+        }
+    }
+}
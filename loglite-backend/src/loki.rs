@@ -0,0 +1,302 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sha2::{Digest, Sha256};
+
+use crate::models::IngestEvent;
+use crate::utils::level_to_severity;
+
+/// Loki `logproto.PushRequest`, as sent snappy-compressed by Promtail and Grafana Agent.
+///
+/// Field tags follow `pkg/push/push.proto`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PushRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub streams: Vec<StreamAdapter>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreamAdapter {
+    #[prost(string, tag = "1")]
+    pub labels: String,
+    #[prost(message, repeated, tag = "2")]
+    pub entries: Vec<EntryAdapter>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EntryAdapter {
+    #[prost(message, optional, tag = "1")]
+    pub timestamp: Option<Timestamp>,
+    #[prost(string, tag = "2")]
+    pub line: String,
+    #[prost(message, repeated, tag = "3")]
+    pub structured_metadata: Vec<LabelPairAdapter>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LabelPairAdapter {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+/// `google.protobuf.Timestamp`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Timestamp {
+    #[prost(int64, tag = "1")]
+    pub seconds: i64,
+    #[prost(int32, tag = "2")]
+    pub nanos: i32,
+}
+
+/// A decoded stream: its label set and `(timestamp, line, structured metadata)` entries.
+#[derive(Debug, Clone)]
+pub struct LokiStream {
+    pub labels: Vec<(String, String)>,
+    pub entries: Vec<LokiEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LokiEntry {
+    pub ts: Option<DateTimeWithTimeZone>,
+    pub line: String,
+    pub metadata: Vec<(String, String)>,
+}
+
+impl LokiStream {
+    /// Value of a stream label.
+    pub fn label(&self, name: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Idempotency key of the entry at `index`, hashed from the stream labels and the entry's
+    /// timestamp and line, so a re-sent push stores nothing new.
+    fn entry_key(&self, entry: &LokiEntry, index: usize) -> String {
+        let mut hasher = Sha256::new();
+        for (k, v) in &self.labels {
+            hasher.update(k.as_bytes());
+            hasher.update([0]);
+            hasher.update(v.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update([0]);
+        if let Some(ts) = entry.ts {
+            hasher.update(ts.timestamp_nanos_opt().unwrap_or_default().to_le_bytes());
+        }
+        hasher.update([0]);
+        hasher.update(entry.line.as_bytes());
+        hasher.update(index.to_le_bytes());
+        format!("loki:{}", hex::encode(hasher.finalize()))
+    }
+
+    /// Convert every entry of the stream into an ingest event.
+    ///
+    /// `host` and `job` labels become the event host and source; all labels and the entry's
+    /// structured metadata are stored in `fields`. Severity is taken from a `level` or
+    /// `detected_level` label or metadata key when present. Each event is keyed with
+    /// [`entry_key`](Self::entry_key).
+    pub fn to_ingest_events(&self) -> Vec<IngestEvent> {
+        let host = self.label("host").unwrap_or_default().to_string();
+        let source = self
+            .label("job")
+            .or_else(|| self.label("filename"))
+            .unwrap_or("loki")
+            .to_string();
+
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let mut fields = serde_json::Map::new();
+                for (k, v) in self.labels.iter().chain(entry.metadata.iter()) {
+                    fields.insert(k.clone(), serde_json::Value::String(v.clone()));
+                }
+                let severity = ["level", "detected_level", "severity"]
+                    .iter()
+                    .find_map(|k| fields.get(*k).and_then(|v| v.as_str()))
+                    .and_then(level_to_severity);
+
+                IngestEvent {
+                    ts: entry.ts.unwrap_or_else(default_ts),
+                    host: host.clone(),
+                    source: source.clone(),
+                    sourcetype: Some("loki".to_string()),
+                    severity,
+                    message: entry.line.clone(),
+                    fields: serde_json::Value::Object(fields),
+                    idempotency_key: Some(self.entry_key(entry, i)),
+                }
+            })
+            .collect()
+    }
+}
+
+fn default_ts() -> DateTimeWithTimeZone {
+    Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+fn nanos_to_ts(secs: i64, nanos: u32) -> Option<DateTimeWithTimeZone> {
+    let utc = DateTime::<Utc>::from_timestamp(secs, nanos)?;
+    Some(utc.with_timezone(&FixedOffset::east_opt(0).unwrap()))
+}
+
+/// Decode a snappy block-compressed protobuf push request.
+pub fn decode_protobuf_push(body: &[u8]) -> Result<Vec<LokiStream>> {
+    use prost::Message;
+
+    let raw = snap::raw::Decoder::new().decompress_vec(body)?;
+    let request = PushRequest::decode(raw.as_slice())?;
+
+    request
+        .streams
+        .into_iter()
+        .map(|stream| {
+            let labels = parse_label_selector(&stream.labels)?;
+            let entries = stream
+                .entries
+                .into_iter()
+                .map(|e| LokiEntry {
+                    ts: e
+                        .timestamp
+                        .and_then(|t| nanos_to_ts(t.seconds, t.nanos.max(0) as u32)),
+                    line: e.line,
+                    metadata: e
+                        .structured_metadata
+                        .into_iter()
+                        .map(|p| (p.name, p.value))
+                        .collect(),
+                })
+                .collect();
+            Ok(LokiStream { labels, entries })
+        })
+        .collect()
+}
+
+/// Decode the JSON push form:
+/// `{"streams": [{"stream": {"job": "x"}, "values": [["<unix ns>", "line", {...}]]}]}`.
+pub fn decode_json_push(body: &[u8]) -> Result<Vec<LokiStream>> {
+    let json: serde_json::Value = serde_json::from_slice(body)?;
+    let streams = json
+        .get("streams")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow!("push request is missing streams"))?;
+
+    streams
+        .iter()
+        .map(|stream| {
+            let labels = string_pairs(stream.get("stream"));
+            let values = stream
+                .get("values")
+                .and_then(|v| v.as_array())
+                .ok_or_else(|| anyhow!("stream is missing values"))?;
+
+            let entries = values
+                .iter()
+                .map(|value| {
+                    let value = value
+                        .as_array()
+                        .ok_or_else(|| anyhow!("stream value is not an array"))?;
+                    let ts_nanos: i128 = value
+                        .first()
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| anyhow!("stream value is missing timestamp"))?
+                        .parse()?;
+                    let line = value
+                        .get(1)
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| anyhow!("stream value is missing line"))?;
+                    Ok(LokiEntry {
+                        ts: nanos_to_ts(
+                            (ts_nanos / 1_000_000_000) as i64,
+                            (ts_nanos % 1_000_000_000) as u32,
+                        ),
+                        line: line.to_string(),
+                        metadata: string_pairs(value.get(2)),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(LokiStream { labels, entries })
+        })
+        .collect()
+}
+
+fn string_pairs(v: Option<&serde_json::Value>) -> Vec<(String, String)> {
+    v.and_then(|v| v.as_object())
+        .map(|obj| {
+            obj.iter()
+                .map(|(k, v)| {
+                    let v = match v {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    (k.clone(), v)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse a Prometheus-style label set such as `{job="varlogs", host="web-1"}`.
+pub fn parse_label_selector(s: &str) -> Result<Vec<(String, String)>> {
+    let s = s.trim();
+    let inner = s
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .ok_or_else(|| anyhow!("label set must be enclosed in braces"))?;
+
+    let mut labels = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace() || *c == ',') {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c.is_whitespace() {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.next() != Some('=') || name.is_empty() {
+            bail!("invalid label set: {}", s);
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.next() != Some('"') {
+            bail!("label value must be quoted: {}", s);
+        }
+
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                Some('\\') => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c) => value.push(c),
+                    None => bail!("unterminated label value: {}", s),
+                },
+                Some('"') => break,
+                Some(c) => value.push(c),
+                None => bail!("unterminated label value: {}", s),
+            }
+        }
+        labels.push((name, value));
+    }
+
+    Ok(labels)
+}
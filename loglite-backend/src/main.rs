@@ -4,26 +4,61 @@ extern crate rocket;
 use std::sync::Arc;
 use tracing_subscriber::EnvFilter;
 
+mod access_log;
+mod beats;
 mod db;
+mod elastic;
 mod entities;
+mod filter;
+mod forward;
+mod gelf;
+mod grok;
 mod handlers;
 mod id_gen;
+mod listener;
+mod loki;
 mod models;
+mod otlp;
+mod parser;
+mod pipeline;
+mod quota;
+mod redaction;
 mod search_engine;
 mod state;
+mod store;
+mod syslog;
 mod tail;
 mod utils;
+mod wal;
 
+use beats::beats_listener_loop;
 use db::init_db;
+use filter::FilterRegistry;
+use forward::forward_listener_loop;
+use gelf::gelf_listener_loop;
 use handlers::{
-    create_app, create_source, delete_source, get_source, health_handler, ingest_auto, ingest_go,
-    ingest_handler, ingest_java, ingest_nginx, ingest_rust, list_apps, list_sources,
-    search_handler_fn, ttl_cleanup_loop, update_source,
+    app_usage, create_app, create_filter_rule, create_parser, create_pipeline,
+    create_redaction_rule, create_source, delete_app_quota, delete_filter_rule, delete_parser,
+    delete_pipeline, delete_redaction_rule, delete_source, dry_run_pipeline, elastic_bulk,
+    elastic_bulk_index, elastic_info, filter_stats, get_app_quota, get_filter_rule, get_parser,
+    get_pipeline, get_redaction_rule, get_source, health_handler, hec_collector, hec_event,
+    hec_health, hec_raw, ingest_auto, ingest_gelf, ingest_go, ingest_handler, ingest_java,
+    ingest_ndjson, ingest_nginx, ingest_python, ingest_rust, list_apps, list_filter_rules,
+    list_grok_patterns, list_parsers, list_pipelines, list_redaction_rules, list_sources,
+    loki_push, otlp_logs, queue_stats, redaction_stats, replay_dead_letters, search_handler_fn,
+    set_app_quota, test_parser, ttl_cleanup_loop, update_filter_rule, update_parser,
+    update_pipeline, update_redaction_rule, update_source,
 };
 use id_gen::Snowflake;
-use search_engine::init_search;
+use parser::ParserRegistry;
+use pipeline::PipelineRegistry;
+use quota::QuotaRegistry;
+use redaction::RedactionRegistry;
+use search_engine::{group_commit_loop, init_search};
 use state::AppState;
+use syslog::syslog_listener_loop;
 use tail::tail_ingestion_loop;
+use wal::{wal_drain_loop, Wal, WalConfig};
 
 #[rocket::main]
 async fn main() -> Result<(), rocket::Error> {
@@ -45,14 +80,51 @@ async fn main() -> Result<(), rocket::Error> {
         .unwrap_or(1);
     let ids = Arc::new(Snowflake::new(node_id));
 
+    let wal_config = WalConfig::from_env();
+    let wal = wal_config
+        .enabled
+        .then(|| Arc::new(Wal::open(&wal_config).expect("failed to open ingest WAL")));
+
+    let pipelines = Arc::new(PipelineRegistry::default());
+    pipelines
+        .reload(&db)
+        .await
+        .expect("failed to load ingest pipelines");
+    let filters = Arc::new(FilterRegistry::default());
+    filters
+        .reload(&db)
+        .await
+        .expect("failed to load filter rules");
+    let redaction = Arc::new(RedactionRegistry::default());
+    redaction
+        .reload(&db)
+        .await
+        .expect("failed to load redaction rules");
+    let quotas = Arc::new(QuotaRegistry::default());
+    quotas.reload(&db).await.expect("failed to load app quotas");
+    let parsers = Arc::new(ParserRegistry::default());
+    parsers.reload(&db).await.expect("failed to load parsers");
+
     let state = AppState {
         db: Arc::new(db),
         search: Arc::new(search),
         ids,
+        wal,
+        pipelines,
+        filters,
+        redaction,
+        quotas,
+        parsers,
     };
 
+    tokio::spawn(group_commit_loop(state.search.clone()));
+    tokio::spawn(wal_drain_loop(Arc::new(state.clone())));
     tokio::spawn(ttl_cleanup_loop(Arc::new(state.clone())));
     tokio::spawn(tail_ingestion_loop(Arc::new(state.clone())));
+    tokio::spawn(syslog_listener_loop(Arc::new(state.clone())));
+    tokio::spawn(gelf_listener_loop(Arc::new(state.clone())));
+    tokio::spawn(forward_listener_loop(Arc::new(state.clone())));
+    tokio::spawn(beats_listener_loop(Arc::new(state.clone())));
 
     let cors = rocket_cors::CorsOptions::default()
         .to_cors()
@@ -71,15 +143,63 @@ async fn main() -> Result<(), rocket::Error> {
                 get_source,
                 update_source,
                 delete_source,
+                create_pipeline,
+                list_pipelines,
+                get_pipeline,
+                update_pipeline,
+                delete_pipeline,
+                dry_run_pipeline,
+                create_redaction_rule,
+                list_redaction_rules,
+                get_redaction_rule,
+                update_redaction_rule,
+                delete_redaction_rule,
+                redaction_stats,
+                create_filter_rule,
+                list_filter_rules,
+                get_filter_rule,
+                update_filter_rule,
+                delete_filter_rule,
+                filter_stats,
+                get_app_quota,
+                set_app_quota,
+                delete_app_quota,
+                app_usage,
+                create_parser,
+                list_parsers,
+                list_grok_patterns,
+                test_parser,
+                get_parser,
+                update_parser,
+                delete_parser,
                 ingest_handler,
                 ingest_nginx,
                 ingest_java,
+                ingest_python,
                 ingest_rust,
                 ingest_go,
                 ingest_auto,
+                ingest_ndjson,
+                queue_stats,
+                replay_dead_letters,
+                ingest_gelf,
                 search_handler_fn
             ],
         )
+        .mount(
+            "/",
+            routes![
+                otlp_logs,
+                loki_push,
+                elastic_info,
+                elastic_bulk,
+                elastic_bulk_index,
+                hec_event,
+                hec_collector,
+                hec_raw,
+                hec_health
+            ],
+        )
         .attach(cors)
         .launch()
         .await?;
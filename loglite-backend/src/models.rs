@@ -1,8 +1,13 @@
 use sea_orm::prelude::DateTimeWithTimeZone;
 use serde::{Deserialize, Serialize};
 
+use crate::filter::{Condition, FilterAction};
+use crate::parser::CaptureMapping;
+use crate::pipeline::Processor;
+use crate::redaction::{Detector, RedactAction};
+
 /// Single log event for ingestion.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IngestEvent {
     #[serde(default = "default_ts")]
     pub ts: DateTimeWithTimeZone,
@@ -17,19 +22,37 @@ pub struct IngestEvent {
     pub message: String,
     #[serde(default)]
     pub fields: serde_json::Value,
+    /// Client-supplied key; an event whose key was already stored for the app within the
+    /// dedup window is acknowledged but not stored again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
 }
 
 /// Request payload for ingesting multiple events.
 #[derive(Debug, Deserialize)]
 pub struct IngestRequest {
     pub app_id: String,
-    pub events: Vec<IngestEvent>,
+    /// Events are decoded one by one so a bad event can be reported by index.
+    pub events: Vec<serde_json::Value>,
 }
 
 /// Response after ingesting events.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 pub struct IngestResponse {
     pub accepted: usize,
+    pub rejected: usize,
+    /// Rejection reasons, capped at a fixed number of entries; `rejected` is the full count.
+    pub errors: Vec<IngestError>,
+}
+
+/// Why an event, or the whole batch when `index` is absent, was rejected.
+///
+/// `index` is the event's position in a JSON batch or its zero-based line in a text body.
+#[derive(Debug, Serialize)]
+pub struct IngestError {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub reason: String,
 }
 
 /// Request payload for creating a new application.
@@ -93,6 +116,29 @@ pub struct HealthResponse {
     pub status: &'static str,
 }
 
+/// Ingestion queue (WAL) depth and throughput counters.
+#[derive(Debug, Serialize)]
+pub struct WalStats {
+    pub enabled: bool,
+    pub queued_records: u64,
+    pub queued_events: u64,
+    pub queued_bytes: u64,
+    pub max_bytes: u64,
+    pub drained_events: u64,
+    /// Events moved to the dead-letter directory after the database repeatedly rejected them.
+    pub dead_lettered_events: u64,
+}
+
+/// Outcome of re-queueing dead-lettered WAL batches.
+#[derive(Debug, Serialize)]
+pub struct WalReplay {
+    pub replayed_batches: u64,
+    pub replayed_events: u64,
+    /// Batches still in the dead-letter directory, because the queue filled up or the file
+    /// could not be read.
+    pub remaining_batches: u64,
+}
+
 /// Request payload for creating a log source.
 #[derive(Debug, Deserialize)]
 pub struct CreateSourceRequest {
@@ -107,6 +153,9 @@ pub struct CreateSourceRequest {
     pub include_glob: Option<String>,
     #[serde(default)]
     pub exclude_glob: Option<String>,
+    /// Name of the parser for the source's lines; detected when absent.
+    #[serde(default)]
+    pub parser: Option<String>,
     #[serde(default)]
     pub enabled: Option<bool>,
 }
@@ -124,6 +173,9 @@ pub struct UpdateSourceRequest {
     pub include_glob: Option<String>,
     #[serde(default)]
     pub exclude_glob: Option<String>,
+    /// An empty name removes the source's parser.
+    #[serde(default)]
+    pub parser: Option<String>,
     #[serde(default)]
     pub enabled: Option<bool>,
 }
@@ -139,10 +191,286 @@ pub struct SourceInfo {
     pub encoding: String,
     pub include_glob: Option<String>,
     pub exclude_glob: Option<String>,
+    pub parser: Option<String>,
     pub enabled: bool,
     pub created_at: DateTimeWithTimeZone,
 }
 
+/// Request payload for creating a parser.
+#[derive(Debug, Deserialize)]
+pub struct CreateParserRequest {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub timestamp_format: Option<String>,
+    #[serde(default)]
+    pub mapping: CaptureMapping,
+}
+
+/// Request payload for updating a parser; its name cannot change.
+#[derive(Debug, Deserialize)]
+pub struct UpdateParserRequest {
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub timestamp_format: Option<String>,
+    #[serde(default)]
+    pub mapping: Option<CaptureMapping>,
+}
+
+/// Parser information for API responses.
+#[derive(Debug, Serialize)]
+pub struct ParserInfo {
+    pub id: i64,
+    pub name: String,
+    pub pattern: String,
+    pub timestamp_format: Option<String>,
+    pub mapping: serde_json::Value,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// Request payload for trying a parser on sample lines.
+///
+/// Uses `pattern` when given, otherwise the stored parser `name`.
+#[derive(Debug, Deserialize)]
+pub struct ParserTestRequest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub timestamp_format: Option<String>,
+    #[serde(default)]
+    pub mapping: CaptureMapping,
+    pub lines: Vec<String>,
+}
+
+/// One sample line as parsed; `entry` is absent when the line did not match.
+#[derive(Debug, Serialize)]
+pub struct ParserTestResult {
+    pub line: String,
+    pub entry: Option<ParsedLine>,
+}
+
+/// Event columns parsed from a line.
+#[derive(Debug, Serialize)]
+pub struct ParsedLine {
+    pub ts: DateTimeWithTimeZone,
+    pub level: String,
+    pub severity: Option<i32>,
+    pub message: String,
+    pub fields: serde_json::Value,
+}
+
+/// Request payload for creating an ingest pipeline.
+#[derive(Debug, Deserialize)]
+pub struct CreatePipelineRequest {
+    pub app_id: String,
+    /// Run only on events from this source; app-wide when absent.
+    #[serde(default)]
+    pub source_id: Option<i64>,
+    pub name: String,
+    pub processors: Vec<Processor>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Request payload for updating an ingest pipeline.
+#[derive(Debug, Deserialize)]
+pub struct UpdatePipelineRequest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub processors: Option<Vec<Processor>>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Pipeline information for API responses.
+#[derive(Debug, Serialize)]
+pub struct PipelineInfo {
+    pub id: i64,
+    pub app_id: String,
+    pub source_id: Option<i64>,
+    pub name: String,
+    pub processors: serde_json::Value,
+    pub enabled: bool,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// Request payload for a pipeline dry run.
+///
+/// Runs `processors` when given, otherwise the enabled pipelines stored for `app_id` and
+/// `source_id`.
+#[derive(Debug, Deserialize)]
+pub struct PipelineDryRunRequest {
+    #[serde(default)]
+    pub processors: Option<Vec<Processor>>,
+    #[serde(default)]
+    pub app_id: Option<String>,
+    #[serde(default)]
+    pub source_id: Option<i64>,
+    pub events: Vec<IngestEvent>,
+}
+
+/// Request payload for creating a redaction rule.
+#[derive(Debug, Deserialize)]
+pub struct CreateRedactionRuleRequest {
+    pub app_id: String,
+    pub name: String,
+    pub detector: Detector,
+    /// Required for the `regex` detector.
+    #[serde(default)]
+    pub pattern: Option<String>,
+    pub action: RedactAction,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Request payload for updating a redaction rule.
+#[derive(Debug, Deserialize)]
+pub struct UpdateRedactionRuleRequest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub action: Option<RedactAction>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Redaction rule information for API responses.
+#[derive(Debug, Serialize)]
+pub struct RedactionRuleInfo {
+    pub id: i64,
+    pub app_id: String,
+    pub name: String,
+    pub detector: String,
+    pub pattern: Option<String>,
+    pub action: String,
+    pub enabled: bool,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// Redaction counters since startup.
+#[derive(Debug, Serialize)]
+pub struct RedactionStats {
+    /// Events with at least one redacted value.
+    pub redacted_events: u64,
+    /// Values masked, hashed or dropped.
+    pub redactions: u64,
+    pub rules: Vec<RedactionRuleStats>,
+}
+
+/// Redaction count of a single rule.
+#[derive(Debug, Serialize)]
+pub struct RedactionRuleStats {
+    pub id: i64,
+    pub app_id: String,
+    pub name: String,
+    pub redactions: u64,
+}
+
+/// Request payload for creating a filter rule.
+#[derive(Debug, Deserialize)]
+pub struct CreateFilterRuleRequest {
+    pub app_id: String,
+    /// Apply only to events from this source; app-wide when absent.
+    #[serde(default)]
+    pub source_id: Option<i64>,
+    pub name: String,
+    #[serde(default)]
+    pub condition: Condition,
+    pub action: FilterAction,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Request payload for updating a filter rule.
+#[derive(Debug, Deserialize)]
+pub struct UpdateFilterRuleRequest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub condition: Option<Condition>,
+    #[serde(default)]
+    pub action: Option<FilterAction>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Filter rule information for API responses.
+#[derive(Debug, Serialize)]
+pub struct FilterRuleInfo {
+    pub id: i64,
+    pub app_id: String,
+    pub source_id: Option<i64>,
+    pub name: String,
+    pub condition: serde_json::Value,
+    pub action: serde_json::Value,
+    pub enabled: bool,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// Events dropped by filter rules since startup.
+#[derive(Debug, Serialize)]
+pub struct FilterStats {
+    pub dropped_events: u64,
+    pub rules: Vec<FilterRuleStats>,
+}
+
+/// Drop count of a single filter rule.
+#[derive(Debug, Serialize)]
+pub struct FilterRuleStats {
+    pub id: i64,
+    pub app_id: String,
+    pub source_id: Option<i64>,
+    pub name: String,
+    pub dropped: u64,
+}
+
+/// Per-app ingestion limits; a `None` limit is unlimited. Also the request payload for
+/// setting an app's quota.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    #[serde(default)]
+    pub events_per_sec: Option<u64>,
+    #[serde(default)]
+    pub bytes_per_sec: Option<u64>,
+    /// Events per UTC day.
+    #[serde(default)]
+    pub daily_events: Option<u64>,
+    /// Bytes (message plus serialized fields) per UTC day.
+    #[serde(default)]
+    pub daily_bytes: Option<u64>,
+}
+
+/// Quota information for API responses.
+#[derive(Debug, Serialize)]
+pub struct AppQuotaInfo {
+    pub app_id: String,
+    #[serde(flatten)]
+    pub limits: QuotaLimits,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+/// Ingestion of an app against its limits.
+#[derive(Debug, Serialize)]
+pub struct AppUsage {
+    pub app_id: String,
+    pub limits: QuotaLimits,
+    /// Events and bytes accepted during the last whole second.
+    pub events_per_sec: u64,
+    pub bytes_per_sec: u64,
+    /// The UTC day the daily counts are for.
+    pub day: chrono::NaiveDate,
+    pub daily_events: u64,
+    pub daily_bytes: u64,
+    /// Events refused for being over a limit since startup.
+    pub throttled_events: u64,
+}
+
 fn default_ts() -> DateTimeWithTimeZone {
     use chrono::{FixedOffset, Utc};
     let utc = Utc::now();
@@ -152,3 +480,15 @@ fn default_ts() -> DateTimeWithTimeZone {
 fn default_limit() -> u64 {
     100
 }
+
+/// Splunk HTTP Event Collector response body.
+#[derive(Debug, Serialize)]
+pub struct HecResponse {
+    pub text: &'static str,
+    pub code: u8,
+    #[serde(
+        rename = "invalid-event-number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub invalid_event_number: Option<usize>,
+}
@@ -0,0 +1,450 @@
+use anyhow::{anyhow, Result};
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use chrono::{DateTime, FixedOffset, Utc};
+use prost::Message;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sha2::{Digest, Sha256};
+
+use crate::models::IngestEvent;
+use crate::utils::level_to_severity;
+
+const LENIENT_PADDING: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+/// Base64 engines for OTLP/JSON `bytes`, which may be standard or URL-safe, padded or not.
+const BASE64_STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, LENIENT_PADDING);
+const BASE64_URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, LENIENT_PADDING);
+
+/// OTLP `ExportLogsServiceRequest`.
+///
+/// Only the messages the `/v1/logs` receiver needs are declared; field tags follow
+/// `opentelemetry/proto/logs/v1/logs.proto` so the wire format stays compatible.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ExportLogsServiceRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub resource_logs: Vec<ResourceLogs>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ExportLogsServiceResponse {
+    #[prost(message, optional, tag = "1")]
+    pub partial_success: Option<ExportLogsPartialSuccess>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ExportLogsPartialSuccess {
+    #[prost(int64, tag = "1")]
+    pub rejected_log_records: i64,
+    #[prost(string, tag = "2")]
+    pub error_message: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ResourceLogs {
+    #[prost(message, optional, tag = "1")]
+    pub resource: Option<Resource>,
+    #[prost(message, repeated, tag = "2")]
+    pub scope_logs: Vec<ScopeLogs>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Resource {
+    #[prost(message, repeated, tag = "1")]
+    pub attributes: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ScopeLogs {
+    #[prost(message, optional, tag = "1")]
+    pub scope: Option<InstrumentationScope>,
+    #[prost(message, repeated, tag = "2")]
+    pub log_records: Vec<LogRecord>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InstrumentationScope {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LogRecord {
+    #[prost(fixed64, tag = "1")]
+    pub time_unix_nano: u64,
+    #[prost(fixed64, tag = "11")]
+    pub observed_time_unix_nano: u64,
+    #[prost(int32, tag = "2")]
+    pub severity_number: i32,
+    #[prost(string, tag = "3")]
+    pub severity_text: String,
+    #[prost(message, optional, tag = "5")]
+    pub body: Option<AnyValue>,
+    #[prost(message, repeated, tag = "6")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(fixed32, tag = "8")]
+    pub flags: u32,
+    #[prost(bytes = "vec", tag = "9")]
+    pub trace_id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "10")]
+    pub span_id: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KeyValue {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<AnyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AnyValue {
+    #[prost(oneof = "any_value::Value", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub value: Option<any_value::Value>,
+}
+
+pub mod any_value {
+    // Variant names mirror the upstream generated types.
+    #[allow(clippy::enum_variant_names)]
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Value {
+        #[prost(string, tag = "1")]
+        StringValue(String),
+        #[prost(bool, tag = "2")]
+        BoolValue(bool),
+        #[prost(int64, tag = "3")]
+        IntValue(i64),
+        #[prost(double, tag = "4")]
+        DoubleValue(f64),
+        #[prost(message, tag = "5")]
+        ArrayValue(super::ArrayValue),
+        #[prost(message, tag = "6")]
+        KvlistValue(super::KeyValueList),
+        #[prost(bytes = "vec", tag = "7")]
+        BytesValue(Vec<u8>),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ArrayValue {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<AnyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KeyValueList {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<KeyValue>,
+}
+
+impl AnyValue {
+    /// Convert to plain JSON. Bytes are hex encoded.
+    pub fn to_json(&self) -> serde_json::Value {
+        use any_value::Value;
+        match &self.value {
+            None => serde_json::Value::Null,
+            Some(Value::StringValue(s)) => serde_json::Value::String(s.clone()),
+            Some(Value::BoolValue(b)) => serde_json::Value::Bool(*b),
+            Some(Value::IntValue(i)) => serde_json::Value::from(*i),
+            Some(Value::DoubleValue(d)) => serde_json::Value::from(*d),
+            Some(Value::ArrayValue(a)) => {
+                serde_json::Value::Array(a.values.iter().map(|v| v.to_json()).collect())
+            }
+            Some(Value::KvlistValue(kv)) => attributes_to_json(&kv.values),
+            Some(Value::BytesValue(b)) => serde_json::Value::String(hex::encode(b)),
+        }
+    }
+
+    /// Render as message text: strings verbatim, everything else as JSON.
+    fn to_message(&self) -> String {
+        match &self.value {
+            Some(any_value::Value::StringValue(s)) => s.clone(),
+            None => String::new(),
+            _ => self.to_json().to_string(),
+        }
+    }
+}
+
+/// Flatten an attribute list into a JSON object.
+pub fn attributes_to_json(attrs: &[KeyValue]) -> serde_json::Value {
+    let mut map = serde_json::Map::with_capacity(attrs.len());
+    for kv in attrs {
+        let value = kv
+            .value
+            .as_ref()
+            .map(|v| v.to_json())
+            .unwrap_or(serde_json::Value::Null);
+        map.insert(kv.key.clone(), value);
+    }
+    serde_json::Value::Object(map)
+}
+
+/// Look up a string attribute by key.
+pub fn string_attribute<'a>(attrs: &'a [KeyValue], key: &str) -> Option<&'a str> {
+    attrs.iter().find(|kv| kv.key == key).and_then(|kv| {
+        match kv.value.as_ref()?.value.as_ref()? {
+            any_value::Value::StringValue(s) if !s.is_empty() => Some(s.as_str()),
+            _ => None,
+        }
+    })
+}
+
+/// Map an OTel `severity_number` (1-24) onto the syslog scale used by `level_to_severity`.
+///
+/// Falls back to `severity_text` when the number is unspecified.
+pub fn otel_severity(severity_number: i32, severity_text: &str) -> Option<i32> {
+    let level = match severity_number {
+        1..=4 => "TRACE",
+        5..=8 => "DEBUG",
+        9..=12 => "INFO",
+        13..=16 => "WARN",
+        17..=20 => "ERROR",
+        21..=24 => "FATAL",
+        _ => severity_text,
+    };
+    level_to_severity(level)
+}
+
+fn nanos_to_ts(nanos: u64) -> Option<DateTimeWithTimeZone> {
+    if nanos == 0 {
+        return None;
+    }
+    let utc = DateTime::<Utc>::from_timestamp(
+        (nanos / 1_000_000_000) as i64,
+        (nanos % 1_000_000_000) as u32,
+    )?;
+    Some(utc.with_timezone(&FixedOffset::east_opt(0).unwrap()))
+}
+
+fn default_ts() -> DateTimeWithTimeZone {
+    Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+impl LogRecord {
+    /// Convert the record into an ingest event.
+    ///
+    /// Returns `None` for records carrying neither a body nor attributes.
+    pub fn to_ingest_event(
+        &self,
+        resource: &[KeyValue],
+        scope: Option<&InstrumentationScope>,
+    ) -> Option<IngestEvent> {
+        if self.body.is_none() && self.attributes.is_empty() {
+            return None;
+        }
+
+        let mut fields = attributes_to_json(&self.attributes);
+        if !self.trace_id.is_empty() {
+            fields["trace_id"] = serde_json::Value::String(hex::encode(&self.trace_id));
+        }
+        if !self.span_id.is_empty() {
+            fields["span_id"] = serde_json::Value::String(hex::encode(&self.span_id));
+        }
+        if self.flags != 0 {
+            fields["trace_flags"] = serde_json::Value::from(self.flags);
+        }
+        if !self.severity_text.is_empty() {
+            fields["severity_text"] = serde_json::Value::String(self.severity_text.clone());
+        }
+        if !resource.is_empty() {
+            fields["resource"] = attributes_to_json(resource);
+        }
+        if let Some(scope) = scope.filter(|s| !s.name.is_empty()) {
+            fields["scope"] = serde_json::json!({
+                "name": scope.name,
+                "version": scope.version,
+            });
+        }
+
+        let source = string_attribute(resource, "service.name")
+            .or(scope.map(|s| s.name.as_str()).filter(|s| !s.is_empty()))
+            .unwrap_or("otlp");
+
+        Some(IngestEvent {
+            ts: nanos_to_ts(self.time_unix_nano)
+                .or_else(|| nanos_to_ts(self.observed_time_unix_nano))
+                .unwrap_or_else(default_ts),
+            host: string_attribute(resource, "host.name")
+                .unwrap_or_default()
+                .to_string(),
+            source: source.to_string(),
+            sourcetype: Some("otlp".to_string()),
+            severity: otel_severity(self.severity_number, &self.severity_text),
+            message: self
+                .body
+                .as_ref()
+                .map(|b| b.to_message())
+                .unwrap_or_default(),
+            fields,
+            idempotency_key: None,
+        })
+    }
+}
+
+/// Idempotency key of the record at `index` in its scope, hashed from the record, its scope
+/// and its resource, so re-exporting the same request stores nothing new.
+pub fn record_key(
+    resource: &[KeyValue],
+    scope: Option<&InstrumentationScope>,
+    record: &LogRecord,
+    index: usize,
+) -> String {
+    let mut hasher = Sha256::new();
+    for attr in resource {
+        hasher.update(attr.encode_to_vec());
+    }
+    hasher.update([0]);
+    if let Some(scope) = scope {
+        hasher.update(scope.encode_to_vec());
+    }
+    hasher.update([0]);
+    hasher.update(record.encode_to_vec());
+    hasher.update(index.to_le_bytes());
+    format!("otlp:{}", hex::encode(hasher.finalize()))
+}
+
+/// Decode the OTLP/JSON encoding of `ExportLogsServiceRequest`.
+///
+/// Follows the OTLP JSON mapping: lowerCamelCase keys, 64-bit integers as strings or numbers,
+/// trace/span ids as hex, and enums as integers. snake_case keys are accepted as well.
+pub fn decode_json_request(body: &[u8]) -> Result<ExportLogsServiceRequest> {
+    let json: serde_json::Value = serde_json::from_slice(body)?;
+    let resource_logs = get(&json, "resourceLogs", "resource_logs")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().map(json_resource_logs).collect())
+        .transpose()?
+        .unwrap_or_default();
+    Ok(ExportLogsServiceRequest { resource_logs })
+}
+
+fn get<'a>(v: &'a serde_json::Value, camel: &str, snake: &str) -> Option<&'a serde_json::Value> {
+    v.get(camel).or_else(|| v.get(snake))
+}
+
+fn json_u64(v: Option<&serde_json::Value>) -> u64 {
+    match v {
+        Some(serde_json::Value::String(s)) => s.parse().unwrap_or(0),
+        Some(n) => n.as_u64().unwrap_or(0),
+        None => 0,
+    }
+}
+
+fn json_str(v: Option<&serde_json::Value>) -> String {
+    v.and_then(|v| v.as_str()).unwrap_or_default().to_string()
+}
+
+fn json_hex(v: Option<&serde_json::Value>) -> Result<Vec<u8>> {
+    match v.and_then(|v| v.as_str()) {
+        Some(s) if !s.is_empty() => hex::decode(s).map_err(|e| anyhow!("invalid hex id: {}", e)),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn json_resource_logs(v: &serde_json::Value) -> Result<ResourceLogs> {
+    let resource = v
+        .get("resource")
+        .map(|r| -> Result<Resource> {
+            Ok(Resource {
+                attributes: json_attributes(r.get("attributes"))?,
+            })
+        })
+        .transpose()?;
+    let scope_logs = get(v, "scopeLogs", "scope_logs")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().map(json_scope_logs).collect())
+        .transpose()?
+        .unwrap_or_default();
+    Ok(ResourceLogs {
+        resource,
+        scope_logs,
+    })
+}
+
+fn json_scope_logs(v: &serde_json::Value) -> Result<ScopeLogs> {
+    let scope = v.get("scope").map(|s| InstrumentationScope {
+        name: json_str(s.get("name")),
+        version: json_str(s.get("version")),
+    });
+    let log_records = get(v, "logRecords", "log_records")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().map(json_log_record).collect())
+        .transpose()?
+        .unwrap_or_default();
+    Ok(ScopeLogs { scope, log_records })
+}
+
+fn json_log_record(v: &serde_json::Value) -> Result<LogRecord> {
+    Ok(LogRecord {
+        time_unix_nano: json_u64(get(v, "timeUnixNano", "time_unix_nano")),
+        observed_time_unix_nano: json_u64(get(
+            v,
+            "observedTimeUnixNano",
+            "observed_time_unix_nano",
+        )),
+        severity_number: json_u64(get(v, "severityNumber", "severity_number")) as i32,
+        severity_text: json_str(get(v, "severityText", "severity_text")),
+        body: v.get("body").map(json_any_value).transpose()?,
+        attributes: json_attributes(v.get("attributes"))?,
+        flags: json_u64(v.get("flags")) as u32,
+        trace_id: json_hex(get(v, "traceId", "trace_id"))?,
+        span_id: json_hex(get(v, "spanId", "span_id"))?,
+    })
+}
+
+fn json_attributes(v: Option<&serde_json::Value>) -> Result<Vec<KeyValue>> {
+    let Some(arr) = v.and_then(|v| v.as_array()) else {
+        return Ok(Vec::new());
+    };
+    arr.iter()
+        .map(|kv| {
+            Ok(KeyValue {
+                key: json_str(kv.get("key")),
+                value: kv.get("value").map(json_any_value).transpose()?,
+            })
+        })
+        .collect()
+}
+
+fn json_any_value(v: &serde_json::Value) -> Result<AnyValue> {
+    use any_value::Value;
+    let value = if let Some(s) = get(v, "stringValue", "string_value") {
+        Value::StringValue(json_str(Some(s)))
+    } else if let Some(b) = get(v, "boolValue", "bool_value") {
+        Value::BoolValue(b.as_bool().unwrap_or(false))
+    } else if let Some(i) = get(v, "intValue", "int_value") {
+        let i = match i {
+            serde_json::Value::String(s) => s.parse().unwrap_or(0),
+            n => n.as_i64().unwrap_or(0),
+        };
+        Value::IntValue(i)
+    } else if let Some(d) = get(v, "doubleValue", "double_value") {
+        Value::DoubleValue(d.as_f64().unwrap_or(0.0))
+    } else if let Some(a) = get(v, "arrayValue", "array_value") {
+        let values = a
+            .get("values")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().map(json_any_value).collect())
+            .transpose()?
+            .unwrap_or_default();
+        Value::ArrayValue(ArrayValue { values })
+    } else if let Some(kv) = get(v, "kvlistValue", "kvlist_value") {
+        Value::KvlistValue(KeyValueList {
+            values: json_attributes(kv.get("values"))?,
+        })
+    } else if let Some(b) = get(v, "bytesValue", "bytes_value") {
+        // The OTLP JSON mapping encodes `bytes` values as base64 (standard or URL-safe);
+        // decode them so they are stored hex encoded, as from protobuf.
+        let text = json_str(Some(b));
+        let bytes = BASE64_STANDARD
+            .decode(&text)
+            .or_else(|_| BASE64_URL_SAFE.decode(&text))
+            .map_err(|_| anyhow!("invalid base64 in bytesValue"))?;
+        Value::BytesValue(bytes)
+    } else {
+        return Ok(AnyValue { value: None });
+    };
+    Ok(AnyValue { value: Some(value) })
+}
@@ -0,0 +1,206 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{DatabaseConnection, EntityTrait, QueryOrder};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::entities::prelude::*;
+use crate::grok::Grok;
+use crate::utils::{parse_timestamp, LogEntry};
+
+/// Which captures of a parser become the event timestamp, level and message; every other
+/// capture is stored in `fields`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureMapping {
+    #[serde(default = "default_ts_capture")]
+    pub ts: String,
+    #[serde(default = "default_level_capture")]
+    pub level: String,
+    #[serde(default = "default_message_capture")]
+    pub message: String,
+}
+
+impl Default for CaptureMapping {
+    fn default() -> Self {
+        Self {
+            ts: default_ts_capture(),
+            level: default_level_capture(),
+            message: default_message_capture(),
+        }
+    }
+}
+
+fn default_ts_capture() -> String {
+    "timestamp".to_string()
+}
+
+fn default_level_capture() -> String {
+    "level".to_string()
+}
+
+fn default_message_capture() -> String {
+    "message".to_string()
+}
+
+/// A user-defined line parser, ready to run.
+#[derive(Debug)]
+pub struct CompiledParser {
+    pub name: String,
+    grok: Grok,
+    timestamp_format: Option<String>,
+    mapping: CaptureMapping,
+}
+
+impl PartialEq for CompiledParser {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for CompiledParser {}
+
+impl CompiledParser {
+    /// Validate a parser, compiling its pattern.
+    pub fn compile(
+        name: &str,
+        pattern: &str,
+        timestamp_format: Option<&str>,
+        mapping: CaptureMapping,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            name: name.to_string(),
+            grok: Grok::compile(pattern)?,
+            timestamp_format: timestamp_format.map(|f| f.to_string()),
+            mapping,
+        })
+    }
+
+    /// Compile a stored parser.
+    pub fn from_model(model: &crate::entities::parsers::Model) -> Result<Self, String> {
+        let mapping: CaptureMapping =
+            serde_json::from_value(model.mapping.clone()).map_err(|e| e.to_string())?;
+        Self::compile(
+            &model.name,
+            &model.pattern,
+            model.timestamp_format.as_deref(),
+            mapping,
+        )
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.grok.is_match(line)
+    }
+
+    /// Parse one line.
+    ///
+    /// Without a message capture the whole line is the message; a missing or unparseable
+    /// timestamp becomes the current time and a missing level `INFO`.
+    pub fn parse_line(&self, line: &str) -> Option<LogEntry> {
+        let mut timestamp = None;
+        let mut level = None;
+        let mut message = None;
+        let mut fields = serde_json::Map::new();
+        for (field, value) in self.grok.parse(line)? {
+            let text = || match &value {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            if field == self.mapping.ts {
+                timestamp = self.parse_ts(&text());
+            } else if field == self.mapping.level {
+                level = Some(text());
+            } else if field == self.mapping.message {
+                message = Some(text());
+            } else {
+                fields.insert(field.to_string(), value);
+            }
+        }
+
+        Some(LogEntry {
+            timestamp: timestamp
+                .unwrap_or_else(|| Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())),
+            level: level.unwrap_or_else(|| "INFO".to_string()),
+            message: message.unwrap_or_else(|| line.to_string()),
+            stacktrace: None,
+            fields: serde_json::Value::Object(fields),
+        })
+    }
+
+    /// Parse a timestamp with the parser's chrono format, or the built-in formats without one.
+    ///
+    /// `UNIX` and `UNIX_MS` read epoch seconds and milliseconds. A format without a year
+    /// (such as `%b %d %H:%M:%S`) is read in the current year, and one without an offset as
+    /// UTC.
+    fn parse_ts(&self, value: &str) -> Option<DateTimeWithTimeZone> {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let Some(format) = &self.timestamp_format else {
+            return parse_timestamp(value);
+        };
+
+        match format.as_str() {
+            "UNIX" => {
+                let secs = value.parse::<f64>().ok()?;
+                let nanos = (secs.fract() * 1e9) as u32;
+                return DateTime::from_timestamp(secs.trunc() as i64, nanos)
+                    .map(|dt| dt.with_timezone(&utc));
+            }
+            "UNIX_MS" => {
+                return DateTime::from_timestamp_millis(value.parse::<i64>().ok()?)
+                    .map(|dt| dt.with_timezone(&utc));
+            }
+            _ => {}
+        }
+
+        if let Ok(dt) = DateTime::parse_from_str(value, format) {
+            return Some(dt.with_timezone(&utc));
+        }
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(naive.and_utc().with_timezone(&utc));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(value, format) {
+            return Some(date.and_hms_opt(0, 0, 0)?.and_utc().with_timezone(&utc));
+        }
+        let with_year = format!("{} {}", Utc::now().year(), value);
+        NaiveDateTime::parse_from_str(&with_year, &format!("%Y {}", format))
+            .ok()
+            .map(|naive| naive.and_utc().with_timezone(&utc))
+    }
+}
+
+/// User-defined parsers, cached in memory and reloaded whenever they change.
+#[derive(Default)]
+pub struct ParserRegistry {
+    parsers: parking_lot::RwLock<Vec<Arc<CompiledParser>>>,
+}
+
+impl ParserRegistry {
+    /// Reload parsers from the database. Parsers that no longer compile are skipped with an
+    /// error log.
+    pub async fn reload(&self, db: &DatabaseConnection) -> Result<()> {
+        let rows = Parser::find()
+            .order_by_asc(crate::entities::parsers::Column::Id)
+            .all(db)
+            .await?;
+
+        let mut parsers = Vec::with_capacity(rows.len());
+        for row in rows {
+            match CompiledParser::from_model(&row) {
+                Ok(parser) => parsers.push(Arc::new(parser)),
+                Err(e) => tracing::error!("Skipping invalid parser {}: {}", row.name, e),
+            }
+        }
+
+        *self.parsers.write() = parsers;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<CompiledParser>> {
+        self.parsers.read().iter().find(|p| p.name == name).cloned()
+    }
+
+    /// Every parser, in creation order.
+    pub fn all(&self) -> Vec<Arc<CompiledParser>> {
+        self.parsers.read().clone()
+    }
+}
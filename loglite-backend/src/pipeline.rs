@@ -0,0 +1,264 @@
+use anyhow::Result;
+use regex::Regex;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::entities::prelude::*;
+use crate::models::IngestEvent;
+use crate::utils::level_to_severity;
+
+/// Event column a `fields` key can be promoted into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromoteTarget {
+    Host,
+    Source,
+    Sourcetype,
+    Severity,
+    Message,
+}
+
+/// One step of a pipeline, stored as JSON tagged by `type`.
+///
+/// Keys are looked up in `fields` by exact name first, then as a dotted path into nested
+/// objects (`kubernetes.pod.name`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Processor {
+    /// Rename a key in `fields`.
+    Rename { from: String, to: String },
+    /// Drop keys from `fields`.
+    Remove { keys: Vec<String> },
+    /// Move a `fields` value into an event column, keeping the key when `keep` is set.
+    Promote {
+        key: String,
+        target: PromoteTarget,
+        #[serde(default)]
+        keep: bool,
+    },
+    /// Set `sourcetype` when `pattern` matches the message, or the `fields` key `field`.
+    SetSourcetype {
+        pattern: String,
+        sourcetype: String,
+        #[serde(default)]
+        field: Option<String>,
+    },
+    /// Add static values to `fields`, overwriting existing keys.
+    AddTags {
+        tags: serde_json::Map<String, Value>,
+    },
+}
+
+/// A validated, ready-to-run processor list.
+#[derive(Debug)]
+pub struct CompiledPipeline {
+    steps: Vec<(Processor, Option<Regex>)>,
+}
+
+/// Look up `key` in `fields` by exact name, then as a dotted path into nested objects.
+pub fn get_path<'a>(fields: &'a Value, key: &str) -> Option<&'a Value> {
+    if let Some(v) = fields.get(key) {
+        return Some(v);
+    }
+    let (head, rest) = key.split_once('.')?;
+    get_path(fields.get(head)?, rest)
+}
+
+fn take_path(fields: &mut Value, key: &str) -> Option<Value> {
+    let obj = fields.as_object_mut()?;
+    if let Some(v) = obj.remove(key) {
+        return Some(v);
+    }
+    let (head, rest) = key.split_once('.')?;
+    take_path(obj.get_mut(head)?, rest)
+}
+
+fn fields_object(event: &mut IngestEvent) -> &mut serde_json::Map<String, Value> {
+    if !event.fields.is_object() {
+        event.fields = Value::Object(serde_json::Map::new());
+    }
+    event.fields.as_object_mut().unwrap()
+}
+
+fn value_to_string(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn value_to_severity(v: &Value) -> Option<i32> {
+    match v {
+        Value::Number(n) => n.as_i64().map(|n| n as i32),
+        Value::String(s) => s
+            .trim()
+            .parse::<i32>()
+            .ok()
+            .or_else(|| level_to_severity(s)),
+        _ => None,
+    }
+}
+
+impl CompiledPipeline {
+    /// Validate processors, compiling their regexes.
+    pub fn compile(processors: Vec<Processor>) -> Result<Self, String> {
+        let steps = processors
+            .into_iter()
+            .map(|p| {
+                let re = match &p {
+                    Processor::SetSourcetype { pattern, .. } => Some(
+                        Regex::new(pattern)
+                            .map_err(|e| format!("invalid pattern {:?}: {}", pattern, e))?,
+                    ),
+                    _ => None,
+                };
+                Ok((p, re))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self { steps })
+    }
+
+    /// Compile a stored JSON processor list.
+    pub fn from_json(processors: &Value) -> Result<Self, String> {
+        let processors: Vec<Processor> =
+            serde_json::from_value(processors.clone()).map_err(|e| e.to_string())?;
+        Self::compile(processors)
+    }
+
+    /// Run every step over `event`, in order.
+    pub fn apply(&self, event: &mut IngestEvent) {
+        for (processor, re) in &self.steps {
+            match processor {
+                Processor::Rename { from, to } => {
+                    if let Some(v) = take_path(&mut event.fields, from) {
+                        fields_object(event).insert(to.clone(), v);
+                    }
+                }
+                Processor::Remove { keys } => {
+                    for key in keys {
+                        take_path(&mut event.fields, key);
+                    }
+                }
+                Processor::Promote { key, target, keep } => {
+                    let value = if *keep {
+                        get_path(&event.fields, key).cloned()
+                    } else {
+                        take_path(&mut event.fields, key)
+                    };
+                    let Some(value) = value else {
+                        continue;
+                    };
+                    match target {
+                        PromoteTarget::Host => event.host = value_to_string(&value),
+                        PromoteTarget::Source => event.source = value_to_string(&value),
+                        PromoteTarget::Sourcetype => {
+                            event.sourcetype = Some(value_to_string(&value))
+                        }
+                        PromoteTarget::Message => event.message = value_to_string(&value),
+                        PromoteTarget::Severity => {
+                            if let Some(severity) = value_to_severity(&value) {
+                                event.severity = Some(severity);
+                            }
+                        }
+                    }
+                }
+                Processor::SetSourcetype {
+                    sourcetype, field, ..
+                } => {
+                    let subject = match field {
+                        Some(key) => get_path(&event.fields, key).map(value_to_string),
+                        None => Some(event.message.clone()),
+                    };
+                    let matched = match (subject, re) {
+                        (Some(subject), Some(re)) => re.is_match(&subject),
+                        _ => false,
+                    };
+                    if matched {
+                        event.sourcetype = Some(sourcetype.clone());
+                    }
+                }
+                Processor::AddTags { tags } => {
+                    let fields = fields_object(event);
+                    for (k, v) in tags {
+                        fields.insert(k.clone(), v.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Pipelines of one app, each with the source it is limited to.
+type AppPipelines = Vec<(Option<i64>, Arc<CompiledPipeline>)>;
+
+/// Enabled pipelines per app, cached in memory and reloaded whenever they change.
+#[derive(Default)]
+pub struct PipelineRegistry {
+    by_app: parking_lot::RwLock<HashMap<String, AppPipelines>>,
+}
+
+impl PipelineRegistry {
+    /// Reload every enabled pipeline from the database. Pipelines that no longer compile are
+    /// skipped with an error log.
+    pub async fn reload(&self, db: &DatabaseConnection) -> Result<()> {
+        let rows = Pipeline::find()
+            .filter(crate::entities::pipelines::Column::Enabled.eq(true))
+            .order_by_asc(crate::entities::pipelines::Column::Id)
+            .all(db)
+            .await?;
+
+        let mut by_app: HashMap<String, AppPipelines> = HashMap::new();
+        for row in rows {
+            match CompiledPipeline::from_json(&row.processors) {
+                Ok(pipeline) => by_app
+                    .entry(row.app_id)
+                    .or_default()
+                    .push((row.source_id, Arc::new(pipeline))),
+                Err(e) => tracing::error!("Skipping invalid pipeline {}: {}", row.id, e),
+            }
+        }
+
+        *self.by_app.write() = by_app;
+        Ok(())
+    }
+
+    /// Pipelines for events of `app_id` from `source_id`: app-wide pipelines first, then
+    /// those attached to the source, each in creation order.
+    pub fn for_source(&self, app_id: &str, source_id: Option<i64>) -> Vec<Arc<CompiledPipeline>> {
+        let by_app = self.by_app.read();
+        let Some(pipelines) = by_app.get(app_id) else {
+            return Vec::new();
+        };
+
+        let app_wide = pipelines.iter().filter(|(source, _)| source.is_none());
+        let for_source = pipelines
+            .iter()
+            .filter(|(source, _)| source.is_some() && *source == source_id);
+        app_wide.chain(for_source).map(|(_, p)| p.clone()).collect()
+    }
+}
+
+/// Run `pipelines` over a copy of `events`, or return `None` when there is nothing to run.
+pub fn process_events(
+    pipelines: &[Arc<CompiledPipeline>],
+    events: &[IngestEvent],
+) -> Option<Vec<IngestEvent>> {
+    if pipelines.is_empty() {
+        return None;
+    }
+    Some(
+        events
+            .iter()
+            .map(|e| {
+                let mut e = e.clone();
+                for pipeline in pipelines {
+                    pipeline.apply(&mut e);
+                }
+                e
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,325 @@
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use sea_orm::{ConnectionTrait, DatabaseConnection, EntityTrait, Statement};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::entities::prelude::*;
+use crate::models::{AppUsage, IngestEvent, QuotaLimits};
+
+/// Returned by [`QuotaRegistry::admit`] when an app is over one of its limits; nothing from
+/// the batch was accepted.
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    /// The limit that was hit, named as in [`QuotaLimits`].
+    pub limit: &'static str,
+    /// Seconds until the app is back under the limit.
+    pub retry_after: u64,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "app is over its {} quota, retry in {}s",
+            self.limit, self.retry_after
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Size of an event as counted against byte limits: its message plus serialized fields.
+pub fn event_bytes(event: &IngestEvent) -> u64 {
+    (event.message.len() + event.fields.to_string().len()) as u64
+}
+
+impl From<&crate::entities::app_quotas::Model> for QuotaLimits {
+    fn from(model: &crate::entities::app_quotas::Model) -> Self {
+        let limit = |v: Option<i64>| v.map(|v| v.max(0) as u64);
+        Self {
+            events_per_sec: limit(model.events_per_sec),
+            bytes_per_sec: limit(model.bytes_per_sec),
+            daily_events: limit(model.daily_events),
+            daily_bytes: limit(model.daily_bytes),
+        }
+    }
+}
+
+/// Token bucket holding at most one second of its rate.
+///
+/// A batch is admitted while the bucket is not in debt and may take it below zero, so a batch
+/// larger than the rate is paid back over the following seconds instead of never fitting.
+struct Bucket {
+    tokens: f64,
+}
+
+impl Bucket {
+    fn refill(&mut self, rate: u64, elapsed: f64) {
+        self.tokens = (self.tokens + rate as f64 * elapsed).min(rate as f64);
+    }
+
+    /// Seconds until the bucket is out of debt, when it is in debt.
+    fn wait(&self, rate: u64) -> Option<u64> {
+        if self.tokens >= 0.0 {
+            return None;
+        }
+        Some(((-self.tokens / rate.max(1) as f64).ceil() as u64).max(1))
+    }
+}
+
+/// What one app has ingested, for enforcement and the usage API.
+struct Usage {
+    events: Bucket,
+    bytes: Bucket,
+    refilled: Instant,
+    /// Counts of the current and the previous whole second.
+    second: i64,
+    second_events: u64,
+    second_bytes: u64,
+    last_second_events: u64,
+    last_second_bytes: u64,
+    day: NaiveDate,
+    day_events: u64,
+    day_bytes: u64,
+    /// Whether today's counts include events stored before startup.
+    day_seeded: bool,
+    throttled_events: u64,
+}
+
+impl Usage {
+    fn new(limits: &QuotaLimits) -> Self {
+        let now = Utc::now();
+        Self {
+            events: Bucket {
+                tokens: limits.events_per_sec.unwrap_or(0) as f64,
+            },
+            bytes: Bucket {
+                tokens: limits.bytes_per_sec.unwrap_or(0) as f64,
+            },
+            refilled: Instant::now(),
+            second: now.timestamp(),
+            second_events: 0,
+            second_bytes: 0,
+            last_second_events: 0,
+            last_second_bytes: 0,
+            day: now.date_naive(),
+            day_events: 0,
+            day_bytes: 0,
+            day_seeded: false,
+            throttled_events: 0,
+        }
+    }
+
+    /// Bring the buckets and counters up to now.
+    fn advance(&mut self, limits: &QuotaLimits) {
+        let elapsed = self.refilled.elapsed().as_secs_f64();
+        self.refilled = Instant::now();
+        match limits.events_per_sec {
+            Some(rate) => self.events.refill(rate, elapsed),
+            None => self.events.tokens = 0.0,
+        }
+        match limits.bytes_per_sec {
+            Some(rate) => self.bytes.refill(rate, elapsed),
+            None => self.bytes.tokens = 0.0,
+        }
+
+        let now = Utc::now();
+        let second = now.timestamp();
+        if second != self.second {
+            let consecutive = second == self.second + 1;
+            self.last_second_events = if consecutive { self.second_events } else { 0 };
+            self.last_second_bytes = if consecutive { self.second_bytes } else { 0 };
+            self.second = second;
+            self.second_events = 0;
+            self.second_bytes = 0;
+        }
+        if now.date_naive() != self.day {
+            self.day = now.date_naive();
+            self.day_events = 0;
+            self.day_bytes = 0;
+            self.day_seeded = false;
+        }
+    }
+
+    fn check(&self, limits: &QuotaLimits) -> Result<(), QuotaExceeded> {
+        let until_midnight = || {
+            let midnight = self.day.succ_opt().unwrap_or(self.day).and_hms_opt(0, 0, 0);
+            midnight.map_or(1, |m| {
+                (m.and_utc() - Utc::now()).num_seconds().max(1) as u64
+            })
+        };
+        if limits
+            .daily_events
+            .is_some_and(|max| self.day_events >= max)
+        {
+            return Err(QuotaExceeded {
+                limit: "daily_events",
+                retry_after: until_midnight(),
+            });
+        }
+        if limits.daily_bytes.is_some_and(|max| self.day_bytes >= max) {
+            return Err(QuotaExceeded {
+                limit: "daily_bytes",
+                retry_after: until_midnight(),
+            });
+        }
+        if let Some(wait) = limits
+            .events_per_sec
+            .and_then(|rate| self.events.wait(rate))
+        {
+            return Err(QuotaExceeded {
+                limit: "events_per_sec",
+                retry_after: wait,
+            });
+        }
+        if let Some(wait) = limits.bytes_per_sec.and_then(|rate| self.bytes.wait(rate)) {
+            return Err(QuotaExceeded {
+                limit: "bytes_per_sec",
+                retry_after: wait,
+            });
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, limits: &QuotaLimits, events: u64, bytes: u64) {
+        if limits.events_per_sec.is_some() {
+            self.events.tokens -= events as f64;
+        }
+        if limits.bytes_per_sec.is_some() {
+            self.bytes.tokens -= bytes as f64;
+        }
+        self.second_events += events;
+        self.second_bytes += bytes;
+        self.day_events += events;
+        self.day_bytes += bytes;
+    }
+}
+
+/// Per-app ingestion limits, cached in memory and reloaded whenever they change, with the
+/// usage they are enforced against.
+///
+/// Usage is tracked in memory for every app; today's counts are seeded from stored events the
+/// first time an app with a daily limit ingests or its usage is read, so a restart does not
+/// reset them.
+#[derive(Default)]
+pub struct QuotaRegistry {
+    by_app: parking_lot::RwLock<HashMap<String, QuotaLimits>>,
+    usage: parking_lot::Mutex<HashMap<String, Usage>>,
+}
+
+impl QuotaRegistry {
+    /// Reload limits from the database.
+    pub async fn reload(&self, db: &DatabaseConnection) -> Result<()> {
+        let rows = AppQuota::find().all(db).await?;
+        *self.by_app.write() = rows
+            .iter()
+            .map(|row| (row.app_id.clone(), QuotaLimits::from(row)))
+            .collect();
+        Ok(())
+    }
+
+    /// Limits of `app_id`; every limit is `None` when it has no quota.
+    pub fn limits(&self, app_id: &str) -> QuotaLimits {
+        self.by_app.read().get(app_id).cloned().unwrap_or_default()
+    }
+
+    /// Count `events` against the limits of `app_id`, failing with [`QuotaExceeded`] when the
+    /// app is already over one of them.
+    ///
+    /// Limits are checked before the batch is counted, so the batch that crosses a limit is
+    /// admitted whole and the next one is refused.
+    pub async fn admit(
+        &self,
+        db: &DatabaseConnection,
+        app_id: &str,
+        events: &[IngestEvent],
+    ) -> Result<()> {
+        let limits = self.limits(app_id);
+        if limits.daily_events.is_some() || limits.daily_bytes.is_some() {
+            self.seed_day(db, app_id).await?;
+        }
+
+        let bytes = events.iter().map(event_bytes).sum();
+        let mut usage = self.usage.lock();
+        let usage = usage
+            .entry(app_id.to_string())
+            .or_insert_with(|| Usage::new(&limits));
+        usage.advance(&limits);
+        if let Err(e) = usage.check(&limits) {
+            usage.throttled_events += events.len() as u64;
+            return Err(e.into());
+        }
+        usage.record(&limits, events.len() as u64, bytes);
+        Ok(())
+    }
+
+    /// Current usage of `app_id` against its limits.
+    pub async fn usage(&self, db: &DatabaseConnection, app_id: &str) -> Result<AppUsage> {
+        self.seed_day(db, app_id).await?;
+        let limits = self.limits(app_id);
+        let mut usage = self.usage.lock();
+        let usage = usage
+            .entry(app_id.to_string())
+            .or_insert_with(|| Usage::new(&limits));
+        usage.advance(&limits);
+        Ok(AppUsage {
+            app_id: app_id.to_string(),
+            limits,
+            events_per_sec: usage.last_second_events,
+            bytes_per_sec: usage.last_second_bytes,
+            day: usage.day,
+            daily_events: usage.day_events,
+            daily_bytes: usage.day_bytes,
+            throttled_events: usage.throttled_events,
+        })
+    }
+
+    /// Seed today's counts of `app_id` from stored events, once per day.
+    ///
+    /// Event ids start with their creation time in milliseconds, so today's events are those
+    /// with ids from midnight on. Events still queued in the WAL are not seen yet; the
+    /// in-memory counts are kept when they are higher.
+    async fn seed_day(&self, db: &DatabaseConnection, app_id: &str) -> Result<()> {
+        let today = Utc::now().date_naive();
+        if self
+            .usage
+            .lock()
+            .get(app_id)
+            .is_some_and(|u| u.day == today && u.day_seeded)
+        {
+            return Ok(());
+        }
+
+        let midnight_ms = today
+            .and_hms_opt(0, 0, 0)
+            .map_or(0, |m| m.and_utc().timestamp_millis());
+        let stmt = Statement::from_sql_and_values(
+            db.get_database_backend(),
+            "SELECT COUNT(*) AS events, \
+             COALESCE(SUM(octet_length(message) + octet_length(fields::text)), 0)::BIGINT AS bytes \
+             FROM events WHERE app_id = $1 AND id >= $2",
+            [app_id.into(), (midnight_ms << 22).into()],
+        );
+        let (events, bytes) = match db.query_one(stmt).await? {
+            Some(row) => (
+                row.try_get::<i64>("", "events")?,
+                row.try_get::<i64>("", "bytes")?,
+            ),
+            None => (0, 0),
+        };
+
+        let limits = self.limits(app_id);
+        let mut usage = self.usage.lock();
+        let usage = usage
+            .entry(app_id.to_string())
+            .or_insert_with(|| Usage::new(&limits));
+        usage.advance(&limits);
+        if usage.day == today && !usage.day_seeded {
+            usage.day_events = usage.day_events.max(events as u64);
+            usage.day_bytes = usage.day_bytes.max(bytes as u64);
+            usage.day_seeded = true;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use sea_orm::{DatabaseConnection, EntityTrait, QueryOrder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::entities::prelude::*;
+use crate::models::{IngestEvent, RedactionRuleStats, RedactionStats};
+
+lazy_static! {
+    static ref EMAIL: Regex = Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap();
+    /// 13 to 19 digits, optionally grouped by spaces or dashes; matches are Luhn-checked.
+    static ref CREDIT_CARD: Regex = Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap();
+    static ref IP: Regex = Regex::new(concat!(
+        r"\b(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\b",
+        r"|\b(?:[0-9A-Fa-f]{1,4}:){7}[0-9A-Fa-f]{1,4}\b",
+        r"|\b(?:[0-9A-Fa-f]{1,4}:){1,6}(?::[0-9A-Fa-f]{1,4}){1,6}\b",
+    ))
+    .unwrap();
+    /// Only the token is replaced; the `Bearer` scheme is kept.
+    static ref BEARER_TOKEN: Regex = Regex::new(r"(?i)\bbearer\s+([A-Za-z0-9\-._~+/]+=*)").unwrap();
+    /// Salt mixed into `hash` replacements, from `LOGLITE_REDACTION_SALT`.
+    static ref HASH_SALT: String = std::env::var("LOGLITE_REDACTION_SALT").unwrap_or_default();
+}
+
+/// What a redaction rule looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Detector {
+    Email,
+    CreditCard,
+    /// IPv4 and IPv6 addresses.
+    Ip,
+    BearerToken,
+    /// The rule's own `pattern`; when it has a capture group only group 1 is replaced.
+    Regex,
+}
+
+impl Detector {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Detector::Email => "email",
+            Detector::CreditCard => "credit_card",
+            Detector::Ip => "ip",
+            Detector::BearerToken => "bearer_token",
+            Detector::Regex => "regex",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        serde_json::from_value(Value::String(s.to_string())).ok()
+    }
+}
+
+/// What happens to a matched value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactAction {
+    /// Replace it with `[REDACTED]`.
+    Mask,
+    /// Replace it with a salted SHA-256 prefix, so equal values stay correlatable.
+    Hash,
+    /// Cut it from `message`; a `fields` value containing it is removed with its key.
+    Drop,
+}
+
+impl RedactAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RedactAction::Mask => "mask",
+            RedactAction::Hash => "hash",
+            RedactAction::Drop => "drop",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        serde_json::from_value(Value::String(s.to_string())).ok()
+    }
+
+    fn replacement(self, value: &str) -> String {
+        match self {
+            RedactAction::Mask => "[REDACTED]".to_string(),
+            RedactAction::Hash => {
+                let mut hasher = Sha256::new();
+                hasher.update(HASH_SALT.as_bytes());
+                hasher.update(value.as_bytes());
+                format!("sha256:{}", hex::encode(&hasher.finalize()[..8]))
+            }
+            RedactAction::Drop => String::new(),
+        }
+    }
+}
+
+fn luhn_valid(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2 == 1, d * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// A validated, ready-to-run redaction rule.
+pub struct CompiledRule {
+    detector: Detector,
+    action: RedactAction,
+    regex: Regex,
+    hits: Arc<AtomicU64>,
+}
+
+impl CompiledRule {
+    /// Validate a rule, compiling its pattern.
+    pub fn compile(
+        detector: Detector,
+        pattern: Option<&str>,
+        action: RedactAction,
+    ) -> Result<Self, String> {
+        let regex = match detector {
+            Detector::Email => EMAIL.clone(),
+            Detector::CreditCard => CREDIT_CARD.clone(),
+            Detector::Ip => IP.clone(),
+            Detector::BearerToken => BEARER_TOKEN.clone(),
+            Detector::Regex => {
+                let pattern = pattern.ok_or("regex rules need a pattern")?;
+                Regex::new(pattern).map_err(|e| format!("invalid pattern {:?}: {}", pattern, e))?
+            }
+        };
+        Ok(Self {
+            detector,
+            action,
+            regex,
+            hits: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Redact every match in `text`, returning the new text when anything matched.
+    fn redact_str(&self, text: &str, hits: &mut u64) -> Option<String> {
+        let mut count = 0;
+        let out = self.regex.replace_all(text, |caps: &Captures| {
+            let whole = caps.get(0).unwrap();
+            let target = caps.get(1).unwrap_or(whole);
+            if self.detector == Detector::CreditCard && !luhn_valid(target.as_str()) {
+                return whole.as_str().to_string();
+            }
+            count += 1;
+            let start = target.start() - whole.start();
+            let end = target.end() - whole.start();
+            format!(
+                "{}{}{}",
+                &whole.as_str()[..start],
+                self.action.replacement(target.as_str()),
+                &whole.as_str()[end..]
+            )
+        });
+        *hits += count;
+        (count > 0).then(|| out.into_owned())
+    }
+
+    /// Redact string values under `value`, returning false when it should be dropped.
+    fn redact_value(&self, value: &mut Value, hits: &mut u64) -> bool {
+        match value {
+            Value::String(s) => match self.redact_str(s, hits) {
+                Some(_) if self.action == RedactAction::Drop => return false,
+                Some(redacted) => *s = redacted,
+                None => {}
+            },
+            Value::Array(items) => items.retain_mut(|v| self.redact_value(v, hits)),
+            Value::Object(map) => map.retain(|_, v| self.redact_value(v, hits)),
+            _ => {}
+        }
+        true
+    }
+
+    /// Redact `message` and the string values in `fields`, returning the number of matches.
+    fn apply(&self, event: &mut IngestEvent) -> u64 {
+        let mut hits = 0;
+        if let Some(redacted) = self.redact_str(&event.message, &mut hits) {
+            event.message = redacted;
+        }
+        self.redact_value(&mut event.fields, &mut hits);
+        hits
+    }
+}
+
+/// Redaction rules of one app, with the counters they report into.
+pub struct AppRedactor {
+    rules: Vec<CompiledRule>,
+    totals: Arc<RedactionTotals>,
+}
+
+/// Counters across all apps since startup.
+#[derive(Default)]
+struct RedactionTotals {
+    events: AtomicU64,
+    values: AtomicU64,
+}
+
+impl AppRedactor {
+    /// Apply every rule to `events`, in rule order.
+    pub fn redact(&self, events: &mut [IngestEvent]) {
+        let mut redacted_events = 0;
+        let mut hits = vec![0u64; self.rules.len()];
+        for event in events.iter_mut() {
+            let mut redacted = false;
+            for (rule, hits) in self.rules.iter().zip(hits.iter_mut()) {
+                let n = rule.apply(event);
+                *hits += n;
+                redacted |= n > 0;
+            }
+            redacted_events += redacted as u64;
+        }
+
+        let total_hits = hits.iter().sum();
+        for (rule, hits) in self.rules.iter().zip(hits) {
+            if hits > 0 {
+                rule.hits.fetch_add(hits, Ordering::Relaxed);
+            }
+        }
+        self.totals
+            .events
+            .fetch_add(redacted_events, Ordering::Relaxed);
+        self.totals.values.fetch_add(total_hits, Ordering::Relaxed);
+    }
+}
+
+/// Enabled redaction rules per app, cached in memory and reloaded whenever they change.
+///
+/// Counters survive reloads, so a rule keeps its totals when it is edited.
+#[derive(Default)]
+pub struct RedactionRegistry {
+    by_app: parking_lot::RwLock<HashMap<String, Arc<AppRedactor>>>,
+    rule_hits: parking_lot::Mutex<HashMap<i64, Arc<AtomicU64>>>,
+    totals: Arc<RedactionTotals>,
+}
+
+impl RedactionRegistry {
+    /// Reload rules from the database, activating the enabled ones. Rules that no longer
+    /// compile are skipped with an error log.
+    pub async fn reload(&self, db: &DatabaseConnection) -> Result<()> {
+        let rows = RedactionRule::find()
+            .order_by_asc(crate::entities::redaction_rules::Column::Id)
+            .all(db)
+            .await?;
+
+        let mut rules_by_app: HashMap<String, Vec<CompiledRule>> = HashMap::new();
+        let mut rule_hits = self.rule_hits.lock();
+        let mut live_hits = HashMap::new();
+        for row in rows {
+            let hits = rule_hits.remove(&row.id).unwrap_or_default();
+            live_hits.insert(row.id, hits.clone());
+            if !row.enabled {
+                continue;
+            }
+
+            let compiled = match (
+                Detector::parse(&row.detector),
+                RedactAction::parse(&row.action),
+            ) {
+                (Some(detector), Some(action)) => {
+                    CompiledRule::compile(detector, row.pattern.as_deref(), action)
+                }
+                _ => Err("unknown detector or action".to_string()),
+            };
+            match compiled {
+                Ok(rule) => rules_by_app
+                    .entry(row.app_id)
+                    .or_default()
+                    .push(CompiledRule { hits, ..rule }),
+                Err(e) => tracing::error!("Skipping invalid redaction rule {}: {}", row.id, e),
+            }
+        }
+        *rule_hits = live_hits;
+
+        *self.by_app.write() = rules_by_app
+            .into_iter()
+            .map(|(app_id, rules)| {
+                let redactor = AppRedactor {
+                    rules,
+                    totals: self.totals.clone(),
+                };
+                (app_id, Arc::new(redactor))
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Redaction rules for `app_id`, if it has any.
+    pub fn for_app(&self, app_id: &str) -> Option<Arc<AppRedactor>> {
+        self.by_app.read().get(app_id).cloned()
+    }
+
+    /// Totals since startup, with per-rule counts for `rules`. Counts of deleted rules stay
+    /// in the totals.
+    pub fn stats(&self, rules: Vec<crate::entities::redaction_rules::Model>) -> RedactionStats {
+        let rule_hits = self.rule_hits.lock();
+        let rules: Vec<RedactionRuleStats> = rules
+            .into_iter()
+            .map(|r| RedactionRuleStats {
+                redactions: rule_hits
+                    .get(&r.id)
+                    .map_or(0, |h| h.load(Ordering::Relaxed)),
+                id: r.id,
+                app_id: r.app_id,
+                name: r.name,
+            })
+            .collect();
+        RedactionStats {
+            redacted_events: self.totals.events.load(Ordering::Relaxed),
+            redactions: self.totals.values.load(Ordering::Relaxed),
+            rules,
+        }
+    }
+}
@@ -1,6 +1,10 @@
 use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tantivy::schema::{Field, SchemaBuilder, STORED, STRING, TEXT};
-use tantivy::{Index, IndexReader, IndexWriter};
+use tantivy::{Index, IndexReader, IndexWriter, TantivyDocument};
+use tokio::sync::Notify;
 
 /// Tantivy search state with index, reader, writer, and field handles.
 pub struct SearchState {
@@ -13,6 +17,54 @@ pub struct SearchState {
     pub field_host: Field,
     pub field_source: Field,
     pub field_message: Field,
+    /// Documents added since the last commit.
+    pending_docs: AtomicUsize,
+    /// Pending document count that triggers a commit before the visibility interval elapses.
+    commit_max_docs: usize,
+    commit_signal: Notify,
+}
+
+impl SearchState {
+    /// Add documents to the index writer without committing.
+    ///
+    /// They become searchable at the next group commit, which runs every
+    /// `LOGLITE_COMMIT_INTERVAL_MS` or as soon as `LOGLITE_COMMIT_MAX_DOCS` are pending. The
+    /// writer lock can be held by a running commit, so it is taken on a blocking thread.
+    pub async fn add_documents(self: &Arc<Self>, docs: Vec<TantivyDocument>) -> Result<()> {
+        let search = self.clone();
+        tokio::task::spawn_blocking(move || search.add_documents_blocking(docs)).await?
+    }
+
+    fn add_documents_blocking(&self, docs: Vec<TantivyDocument>) -> Result<()> {
+        let added = docs.len();
+        {
+            let writer = self.writer.lock();
+            for d in docs {
+                writer.add_document(d)?;
+            }
+        }
+
+        let pending = self.pending_docs.fetch_add(added, Ordering::AcqRel) + added;
+        if pending >= self.commit_max_docs {
+            self.commit_signal.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Commit pending documents and reload the reader so they become searchable.
+    ///
+    /// The pending count is only reduced once the commit succeeds, so a failed commit is
+    /// retried at the next group commit.
+    pub fn commit_pending(&self) -> Result<()> {
+        let pending = self.pending_docs.load(Ordering::Acquire);
+        if pending == 0 {
+            return Ok(());
+        }
+        self.writer.lock().commit()?;
+        self.pending_docs.fetch_sub(pending, Ordering::AcqRel);
+        self.reader.reload()?;
+        Ok(())
+    }
 }
 
 /// Initialize the Tantivy search index.
@@ -40,6 +92,10 @@ pub fn init_search(index_dir: &str) -> Result<SearchState> {
         .reload_policy(tantivy::ReloadPolicy::Manual)
         .try_into()?;
     let writer = index.writer(50_000_000)?;
+    let commit_max_docs = std::env::var("LOGLITE_COMMIT_MAX_DOCS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(10_000);
 
     Ok(SearchState {
         index,
@@ -51,5 +107,31 @@ pub fn init_search(index_dir: &str) -> Result<SearchState> {
         field_host,
         field_source,
         field_message,
+        pending_docs: AtomicUsize::new(0),
+        commit_max_docs,
+        commit_signal: Notify::new(),
     })
 }
+
+/// Background task that group-commits the search index.
+///
+/// Commits at most every `LOGLITE_COMMIT_INTERVAL_MS` (the visibility latency, 1000 ms by
+/// default), or earlier once the pending document budget is reached.
+pub async fn group_commit_loop(search: Arc<SearchState>) {
+    let interval_ms: u64 = std::env::var("LOGLITE_COMMIT_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(1000);
+    let interval = Duration::from_millis(interval_ms.max(1));
+
+    loop {
+        let _ = tokio::time::timeout(interval, search.commit_signal.notified()).await;
+
+        let search = search.clone();
+        match tokio::task::spawn_blocking(move || search.commit_pending()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::error!("Search index commit failed: {}", e),
+            Err(e) => tracing::error!("Search index commit task failed: {}", e),
+        }
+    }
+}
@@ -1,8 +1,14 @@
 use sea_orm::DatabaseConnection;
 use std::sync::Arc;
 
+use crate::filter::FilterRegistry;
 use crate::id_gen::Snowflake;
+use crate::parser::ParserRegistry;
+use crate::pipeline::PipelineRegistry;
+use crate::quota::QuotaRegistry;
+use crate::redaction::RedactionRegistry;
 use crate::search_engine::SearchState;
+use crate::wal::Wal;
 
 /// Global application state shared across handlers.
 #[derive(Clone)]
@@ -10,4 +16,16 @@ pub struct AppState {
     pub db: Arc<DatabaseConnection>,
     pub search: Arc<SearchState>,
     pub ids: Arc<Snowflake>,
+    /// Durable ingestion queue; `None` when `LOGLITE_WAL_ENABLED=false`.
+    pub wal: Option<Arc<Wal>>,
+    /// Enabled ingest pipelines, reloaded whenever one is created, changed or deleted.
+    pub pipelines: Arc<PipelineRegistry>,
+    /// Enabled sampling, filtering and rate-limit rules and their drop counters.
+    pub filters: Arc<FilterRegistry>,
+    /// Enabled PII redaction rules and their counters.
+    pub redaction: Arc<RedactionRegistry>,
+    /// Per-app ingestion limits and the usage counted against them.
+    pub quotas: Arc<QuotaRegistry>,
+    /// User-defined grok parsers, reloaded whenever one is created, changed or deleted.
+    pub parsers: Arc<ParserRegistry>,
 }
@@ -0,0 +1,220 @@
+use anyhow::Result;
+use chrono::{FixedOffset, Utc};
+use lazy_static::lazy_static;
+use sea_orm::sea_query::{Expr, OnConflict, Query};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseTransaction, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use std::collections::HashSet;
+use tantivy::doc;
+
+use crate::entities::ingest_keys;
+use crate::entities::prelude::*;
+use crate::filter::filter_events;
+use crate::models::IngestEvent;
+use crate::pipeline::process_events;
+use crate::state::AppState;
+
+/// Rows per INSERT statement, keeping each statement well under Postgres' bind parameter limit.
+const INSERT_CHUNK_ROWS: usize = 1000;
+
+lazy_static! {
+    /// How long an idempotency key suppresses duplicates, from `LOGLITE_DEDUP_WINDOW_SECS`.
+    pub static ref DEDUP_WINDOW: chrono::Duration = chrono::Duration::seconds(
+        std::env::var("LOGLITE_DEDUP_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(86_400)
+    );
+}
+
+/// Record `keys` for `app_id` and return those not already seen within the dedup window.
+///
+/// Keys are inserted with `ON CONFLICT`, refreshing only entries older than the window, so
+/// concurrent writers of the same key serialize on its row and exactly one of them claims it.
+async fn claim_keys(
+    txn: &DatabaseTransaction,
+    app_id: &str,
+    keys: &[&str],
+) -> Result<HashSet<String>> {
+    let now = Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap());
+    let cutoff = now - *DEDUP_WINDOW;
+    let mut claimed = HashSet::with_capacity(keys.len());
+
+    for chunk in keys.chunks(INSERT_CHUNK_ROWS) {
+        let mut insert = Query::insert();
+        insert
+            .into_table(IngestKey)
+            .columns([
+                ingest_keys::Column::AppId,
+                ingest_keys::Column::Key,
+                ingest_keys::Column::CreatedAt,
+            ])
+            .on_conflict(
+                OnConflict::columns([ingest_keys::Column::AppId, ingest_keys::Column::Key])
+                    .update_column(ingest_keys::Column::CreatedAt)
+                    .action_and_where(
+                        Expr::col((IngestKey, ingest_keys::Column::CreatedAt)).lt(cutoff),
+                    )
+                    .to_owned(),
+            )
+            .returning_col(ingest_keys::Column::Key);
+        for key in chunk {
+            insert.values_panic([app_id.into(), (*key).into(), now.into()]);
+        }
+
+        let stmt = txn.get_database_backend().build(&insert);
+        for row in txn.query_all(stmt).await? {
+            claimed.insert(row.try_get::<String>("", "key")?);
+        }
+    }
+
+    Ok(claimed)
+}
+
+/// Those of `keys` already stored for `app_id` within the dedup window.
+///
+/// Events still queued in the WAL have not claimed their keys yet, so they are not reported.
+pub async fn stored_keys(state: &AppState, app_id: &str, keys: &[&str]) -> Result<HashSet<String>> {
+    let cutoff = Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap()) - *DEDUP_WINDOW;
+    let mut stored = HashSet::new();
+    for chunk in keys.chunks(INSERT_CHUNK_ROWS) {
+        let rows = IngestKey::find()
+            .filter(ingest_keys::Column::AppId.eq(app_id))
+            .filter(ingest_keys::Column::Key.is_in(chunk.iter().copied()))
+            .filter(ingest_keys::Column::CreatedAt.gte(cutoff))
+            .all(state.db.as_ref())
+            .await?;
+        stored.extend(rows.into_iter().map(|r| r.key));
+    }
+    Ok(stored)
+}
+
+/// Persist events for one app.
+///
+/// Rows are written with multi-row INSERTs in a single transaction, so a batch is stored
+/// entirely or not at all. Events whose idempotency key was already stored within the dedup
+/// window are skipped but still counted as accepted. The matching search documents are
+/// queued for the next group commit rather than committed here.
+pub async fn write_events(state: &AppState, app_id: &str, events: &[IngestEvent]) -> Result<usize> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+
+    let txn = state.db.begin().await?;
+
+    let mut batch_keys: HashSet<&str> = HashSet::new();
+    let keys: Vec<&str> = events
+        .iter()
+        .filter_map(|e| e.idempotency_key.as_deref())
+        .filter(|k| batch_keys.insert(k))
+        .collect();
+    let mut claimed = if keys.is_empty() {
+        HashSet::new()
+    } else {
+        claim_keys(&txn, app_id, &keys).await?
+    };
+
+    let mut rows = Vec::with_capacity(events.len());
+    let mut docs: Vec<tantivy::TantivyDocument> = Vec::with_capacity(events.len());
+
+    for e in events {
+        // A key is claimed once per batch, so repeats inside the batch are dropped too.
+        if let Some(key) = &e.idempotency_key {
+            if !claimed.remove(key) {
+                continue;
+            }
+        }
+
+        let id = state.ids.next_id();
+        rows.push(crate::entities::events::ActiveModel {
+            id: Set(id),
+            app_id: Set(app_id.to_string()),
+            ts: Set(e.ts),
+            host: Set(e.host.clone()),
+            source: Set(e.source.clone()),
+            sourcetype: Set(e.sourcetype.clone()),
+            severity: Set(e.severity),
+            message: Set(e.message.clone()),
+            fields: Set(e.fields.clone()),
+        });
+
+        docs.push(doc!(
+            state.search.field_app_id => app_id,
+            state.search.field_event_id => id,
+            state.search.field_ts_epoch_ms => e.ts.timestamp_millis(),
+            state.search.field_host => e.host.as_str(),
+            state.search.field_source => e.source.as_str(),
+            state.search.field_message => e.message.as_str()
+        ));
+    }
+
+    while !rows.is_empty() {
+        let rest = rows.split_off(rows.len().min(INSERT_CHUNK_ROWS));
+        Event::insert_many(std::mem::replace(&mut rows, rest))
+            .exec(&txn)
+            .await?;
+    }
+    txn.commit().await?;
+
+    if !docs.is_empty() {
+        state.search.add_documents(docs).await?;
+    }
+    Ok(events.len())
+}
+
+/// Accept events for one app: run the app's pipelines, filter rules and redaction rules over
+/// them (including those attached to `source_id`, for tailed files), then queue them in the
+/// WAL when it is enabled, otherwise write them straight to storage.
+///
+/// Events dropped by filter rules are counted as accepted. The rest count against the app's
+/// quota; an app over it fails with [`QuotaExceeded`](crate::quota::QuotaExceeded) and
+/// nothing is stored.
+pub async fn accept_events(
+    state: &AppState,
+    app_id: &str,
+    source_id: Option<i64>,
+    events: &[IngestEvent],
+) -> Result<usize> {
+    let accepted = events.len();
+    let pipelines = state.pipelines.for_source(app_id, source_id);
+    let mut processed = process_events(&pipelines, events);
+
+    let filters = state.filters.for_source(app_id, source_id);
+    if let Some(keep) = filter_events(&filters, processed.as_deref().unwrap_or(events)) {
+        let batch = processed.unwrap_or_else(|| events.to_vec());
+        processed = Some(
+            batch
+                .into_iter()
+                .zip(keep)
+                .filter_map(|(event, keep)| keep.then_some(event))
+                .collect(),
+        );
+    }
+
+    if processed.as_deref().unwrap_or(events).is_empty() {
+        return Ok(accepted);
+    }
+    state
+        .quotas
+        .admit(
+            state.db.as_ref(),
+            app_id,
+            processed.as_deref().unwrap_or(events),
+        )
+        .await?;
+
+    if let Some(redactor) = state.redaction.for_app(app_id) {
+        redactor.redact(processed.get_or_insert_with(|| events.to_vec()));
+    }
+    let events = processed.as_deref().unwrap_or(events);
+
+    match &state.wal {
+        Some(wal) => wal.append(app_id, events).await?,
+        None => {
+            write_events(state, app_id, events).await?;
+        }
+    }
+    Ok(accepted)
+}
@@ -0,0 +1,514 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+
+use crate::db::DEFAULT_APP;
+use crate::listener::{flush_loop, parse_port_list, RoutedEvent};
+use crate::models::IngestEvent;
+use crate::state::AppState;
+
+/// Largest syslog frame accepted over TCP (octet-counted or newline-delimited).
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+/// Longest accepted octet-count prefix, including the trailing space.
+const MAX_LEN_PREFIX_BYTES: u64 = 16;
+
+/// Syslog listener configuration, read from `LOGLITE_SYSLOG_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct SyslogConfig {
+    pub bind: String,
+    pub udp_ports: Vec<u16>,
+    pub tcp_ports: Vec<u16>,
+    pub default_app_id: String,
+    pub port_apps: HashMap<u16, String>,
+    pub flush_interval: Duration,
+}
+
+impl SyslogConfig {
+    /// Load the listener configuration from the environment.
+    ///
+    /// `LOGLITE_SYSLOG_PORT_APPS` uses the form `5514=network,5515=legacy`.
+    pub fn from_env() -> Self {
+        let bind = std::env::var("LOGLITE_SYSLOG_BIND").unwrap_or_else(|_| "0.0.0.0".to_string());
+        let udp_ports =
+            parse_port_list(&std::env::var("LOGLITE_SYSLOG_UDP_PORTS").unwrap_or_default());
+        let tcp_ports =
+            parse_port_list(&std::env::var("LOGLITE_SYSLOG_TCP_PORTS").unwrap_or_default());
+        let default_app_id =
+            std::env::var("LOGLITE_SYSLOG_APP_ID").unwrap_or_else(|_| DEFAULT_APP.to_string());
+        let port_apps = std::env::var("LOGLITE_SYSLOG_PORT_APPS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| {
+                let (port, app_id) = pair.split_once('=')?;
+                let port = port.trim().parse::<u16>().ok()?;
+                let app_id = app_id.trim();
+                if app_id.is_empty() {
+                    return None;
+                }
+                Some((port, app_id.to_string()))
+            })
+            .collect();
+        let flush_interval_ms: u64 = std::env::var("LOGLITE_SYSLOG_FLUSH_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(1000);
+
+        Self {
+            bind,
+            udp_ports,
+            tcp_ports,
+            default_app_id,
+            port_apps,
+            flush_interval: Duration::from_millis(flush_interval_ms),
+        }
+    }
+
+    /// Resolve the app a message received on `port` is ingested into.
+    pub fn app_for_port(&self, port: u16) -> String {
+        self.port_apps
+            .get(&port)
+            .cloned()
+            .unwrap_or_else(|| self.default_app_id.clone())
+    }
+}
+
+/// Syslog framing flavour a message was parsed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyslogFormat {
+    Rfc5424,
+    Rfc3164,
+}
+
+/// A parsed syslog message.
+#[derive(Debug, Clone)]
+pub struct SyslogMessage {
+    pub format: SyslogFormat,
+    pub facility: i32,
+    pub severity: i32,
+    pub timestamp: Option<DateTimeWithTimeZone>,
+    pub hostname: Option<String>,
+    pub app_name: Option<String>,
+    pub proc_id: Option<String>,
+    pub msg_id: Option<String>,
+    pub structured_data: serde_json::Value,
+    pub message: String,
+}
+
+impl SyslogMessage {
+    /// Convert the message into an ingest event.
+    ///
+    /// `peer_host` is used when the message does not carry a HOSTNAME.
+    pub fn into_ingest_event(self, peer_host: &str) -> IngestEvent {
+        let mut fields = serde_json::json!({
+            "facility": self.facility,
+        });
+        if let Some(proc_id) = self.proc_id {
+            fields["procid"] = serde_json::Value::String(proc_id);
+        }
+        if let Some(msg_id) = self.msg_id {
+            fields["msgid"] = serde_json::Value::String(msg_id);
+        }
+        if self
+            .structured_data
+            .as_object()
+            .is_some_and(|sd| !sd.is_empty())
+        {
+            fields["structured_data"] = self.structured_data;
+        }
+
+        let sourcetype = match self.format {
+            SyslogFormat::Rfc5424 => "syslog_rfc5424",
+            SyslogFormat::Rfc3164 => "syslog_rfc3164",
+        };
+
+        IngestEvent {
+            ts: self.timestamp.unwrap_or_else(default_ts),
+            host: self.hostname.unwrap_or_else(|| peer_host.to_string()),
+            source: self.app_name.unwrap_or_else(|| "syslog".to_string()),
+            sourcetype: Some(sourcetype.to_string()),
+            severity: Some(self.severity),
+            message: self.message,
+            fields,
+            idempotency_key: None,
+        }
+    }
+}
+
+fn default_ts() -> DateTimeWithTimeZone {
+    Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())
+}
+
+/// Treat the RFC 5424 NILVALUE (`-`) as absent.
+fn nil_to_none(s: &str) -> Option<String> {
+    if s == "-" || s.is_empty() {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+/// Parse a single syslog message in RFC 5424 or RFC 3164 (BSD) format.
+///
+/// Messages without a valid `<PRI>` header are rejected.
+pub fn parse_syslog_message(raw: &str) -> Option<SyslogMessage> {
+    let raw = raw.trim_end_matches(['\r', '\n', '\0']);
+    let rest = raw.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    if end == 0 || end > 3 {
+        return None;
+    }
+    let pri: i32 = rest[..end].parse().ok()?;
+    if pri > 191 {
+        return None;
+    }
+    let rest = &rest[end + 1..];
+
+    // RFC 5424 messages carry a VERSION ("1") directly after PRI.
+    if let Some(body) = rest.strip_prefix("1 ") {
+        if let Some(msg) = parse_rfc5424(pri, body) {
+            return Some(msg);
+        }
+    }
+
+    Some(parse_rfc3164(pri, rest))
+}
+
+fn parse_rfc5424(pri: i32, body: &str) -> Option<SyslogMessage> {
+    let mut parts = body.splitn(6, ' ');
+    let timestamp = parts.next()?;
+    let hostname = parts.next()?;
+    let app_name = parts.next()?;
+    let proc_id = parts.next()?;
+    let msg_id = parts.next()?;
+    let rest = parts.next().unwrap_or("");
+
+    let timestamp = if timestamp == "-" {
+        None
+    } else {
+        Some(DateTime::parse_from_rfc3339(timestamp).ok()?)
+    };
+
+    let (structured_data, message) = parse_structured_data(rest)?;
+    let message = message.strip_prefix('\u{feff}').unwrap_or(message);
+
+    Some(SyslogMessage {
+        format: SyslogFormat::Rfc5424,
+        facility: pri >> 3,
+        severity: pri & 7,
+        timestamp,
+        hostname: nil_to_none(hostname),
+        app_name: nil_to_none(app_name),
+        proc_id: nil_to_none(proc_id),
+        msg_id: nil_to_none(msg_id),
+        structured_data,
+        message: message.to_string(),
+    })
+}
+
+/// Parse RFC 5424 STRUCTURED-DATA and return it with the remaining MSG.
+///
+/// Each SD-ELEMENT becomes an object keyed by its SD-ID: `{"exampleSDID@32473": {"iut": "3"}}`.
+fn parse_structured_data(s: &str) -> Option<(serde_json::Value, &str)> {
+    let mut sd = serde_json::Map::new();
+
+    if let Some(rest) = s.strip_prefix('-') {
+        return Some((
+            serde_json::Value::Object(sd),
+            rest.strip_prefix(' ').unwrap_or(rest),
+        ));
+    }
+
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() && bytes[i] == b'[' {
+        i += 1;
+        let id_start = i;
+        while i < bytes.len() && bytes[i] != b' ' && bytes[i] != b']' {
+            i += 1;
+        }
+        let sd_id = s.get(id_start..i)?.to_string();
+        let mut params = serde_json::Map::new();
+
+        loop {
+            while i < bytes.len() && bytes[i] == b' ' {
+                i += 1;
+            }
+            if i >= bytes.len() {
+                return None;
+            }
+            if bytes[i] == b']' {
+                i += 1;
+                break;
+            }
+
+            let name_start = i;
+            while i < bytes.len() && bytes[i] != b'=' {
+                i += 1;
+            }
+            let name = s.get(name_start..i)?.to_string();
+            i += 1;
+            if bytes.get(i) != Some(&b'"') {
+                return None;
+            }
+            i += 1;
+
+            let mut value = String::new();
+            let mut seg_start = i;
+            loop {
+                match bytes.get(i)? {
+                    b'\\' if matches!(bytes.get(i + 1), Some(b'"' | b'\\' | b']')) => {
+                        value.push_str(s.get(seg_start..i)?);
+                        seg_start = i + 1;
+                        i += 2;
+                    }
+                    b'"' => {
+                        value.push_str(s.get(seg_start..i)?);
+                        i += 1;
+                        break;
+                    }
+                    _ => i += 1,
+                }
+            }
+            params.insert(name, serde_json::Value::String(value));
+        }
+
+        sd.insert(sd_id, serde_json::Value::Object(params));
+    }
+
+    if sd.is_empty() {
+        return None;
+    }
+
+    let rest = &s[i..];
+    Some((
+        serde_json::Value::Object(sd),
+        rest.strip_prefix(' ').unwrap_or(rest),
+    ))
+}
+
+fn parse_rfc3164(pri: i32, body: &str) -> SyslogMessage {
+    let mut rest = body;
+    let mut timestamp = None;
+    let mut hostname = None;
+
+    // TIMESTAMP is "Mmm dd hh:mm:ss" (day padded with a space), without year or zone.
+    if let Some(ts_str) = body.get(..15) {
+        if let Some(ts) = parse_bsd_timestamp(ts_str) {
+            timestamp = Some(ts);
+            rest = body[15..].trim_start();
+
+            // HOSTNAME follows the timestamp unless the next token already is the TAG.
+            if let Some((host, after)) = rest.split_once(' ') {
+                if !host.ends_with(':') && !host.contains('[') {
+                    hostname = Some(host.to_string());
+                    rest = after;
+                }
+            }
+        }
+    }
+
+    // TAG is alphanumeric, optionally followed by "[pid]", and terminated by ':'.
+    let mut app_name = None;
+    let mut proc_id = None;
+    let tag_end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')))
+        .unwrap_or(rest.len());
+    if tag_end > 0 && tag_end <= 48 {
+        let after_tag = &rest[tag_end..];
+        let (pid, after_pid) = match after_tag.strip_prefix('[') {
+            Some(p) => match p.split_once(']') {
+                Some((pid, after)) => (Some(pid), after),
+                None => (None, after_tag),
+            },
+            None => (None, after_tag),
+        };
+        if let Some(msg) = after_pid.strip_prefix(':') {
+            app_name = Some(rest[..tag_end].to_string());
+            proc_id = pid.map(|p| p.to_string());
+            rest = msg.strip_prefix(' ').unwrap_or(msg);
+        }
+    }
+
+    SyslogMessage {
+        format: SyslogFormat::Rfc3164,
+        facility: pri >> 3,
+        severity: pri & 7,
+        timestamp,
+        hostname,
+        app_name,
+        proc_id,
+        msg_id: None,
+        structured_data: serde_json::json!({}),
+        message: rest.to_string(),
+    }
+}
+
+/// Parse a BSD syslog timestamp, assuming UTC and the current year.
+///
+/// Timestamps that would land more than a day in the future are attributed to the previous
+/// year, which covers messages emitted just before New Year.
+fn parse_bsd_timestamp(s: &str) -> Option<DateTimeWithTimeZone> {
+    let now = Utc::now();
+    let parse_in_year = |year: i32| {
+        NaiveDateTime::parse_from_str(&format!("{} {}", year, s), "%Y %b %e %H:%M:%S").ok()
+    };
+    let mut naive = parse_in_year(now.year())?;
+    if naive > now.naive_utc() + chrono::Duration::days(1) {
+        naive = parse_in_year(now.year() - 1)?;
+    }
+    Some(
+        Utc.from_utc_datetime(&naive)
+            .with_timezone(&FixedOffset::east_opt(0).unwrap()),
+    )
+}
+
+/// Background task running the syslog UDP and TCP listeners.
+///
+/// Listeners are only started for ports listed in `LOGLITE_SYSLOG_UDP_PORTS` /
+/// `LOGLITE_SYSLOG_TCP_PORTS`. Received events are buffered and flushed through the same
+/// ingest path as the HTTP handlers.
+pub async fn syslog_listener_loop(state: Arc<AppState>) {
+    let config = Arc::new(SyslogConfig::from_env());
+    if config.udp_ports.is_empty() && config.tcp_ports.is_empty() {
+        return;
+    }
+
+    let (tx, rx) = mpsc::channel::<RoutedEvent>(10_000);
+
+    for &port in &config.udp_ports {
+        let addr = format!("{}:{}", config.bind, port);
+        match UdpSocket::bind(&addr).await {
+            Ok(socket) => {
+                tracing::info!("Syslog UDP listener on {}", addr);
+                tokio::spawn(run_udp(socket, config.app_for_port(port), tx.clone()));
+            }
+            Err(e) => tracing::error!("Failed to bind syslog UDP {}: {}", addr, e),
+        }
+    }
+
+    for &port in &config.tcp_ports {
+        let addr = format!("{}:{}", config.bind, port);
+        match TcpListener::bind(&addr).await {
+            Ok(listener) => {
+                tracing::info!("Syslog TCP listener on {}", addr);
+                tokio::spawn(run_tcp(listener, config.app_for_port(port), tx.clone()));
+            }
+            Err(e) => tracing::error!("Failed to bind syslog TCP {}: {}", addr, e),
+        }
+    }
+
+    drop(tx);
+    flush_loop(state, rx, config.flush_interval, "syslog").await;
+}
+
+async fn run_udp(socket: UdpSocket, app_id: String, tx: mpsc::Sender<RoutedEvent>) {
+    let mut buf = vec![0u8; 65_536];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::error!("Syslog UDP receive failed: {}", e);
+                continue;
+            }
+        };
+        let raw = String::from_utf8_lossy(&buf[..len]);
+        if let Some(event) = to_event(&raw, &peer) {
+            if tx.send((app_id.clone(), event)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn run_tcp(listener: TcpListener, app_id: String, tx: mpsc::Sender<RoutedEvent>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let app_id = app_id.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_tcp_connection(stream, peer, app_id, tx).await {
+                        tracing::debug!("Syslog TCP connection {} closed: {}", peer, e);
+                    }
+                });
+            }
+            Err(e) => tracing::error!("Syslog TCP accept failed: {}", e),
+        }
+    }
+}
+
+/// Read frames from a TCP connection.
+///
+/// Supports both octet counting (`LEN SP MSG`, RFC 6587 3.4.1) and newline-delimited
+/// non-transparent framing; the mode is detected per frame from its first byte.
+async fn handle_tcp_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    app_id: String,
+    tx: mpsc::Sender<RoutedEvent>,
+) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut frame: Vec<u8> = Vec::new();
+
+    loop {
+        let first = {
+            let buf = reader.fill_buf().await?;
+            match buf.first() {
+                Some(b) => *b,
+                None => return Ok(()),
+            }
+        };
+
+        frame.clear();
+        if first.is_ascii_digit() {
+            let mut len_buf = Vec::new();
+            (&mut reader)
+                .take(MAX_LEN_PREFIX_BYTES)
+                .read_until(b' ', &mut len_buf)
+                .await?;
+            if len_buf.last() != Some(&b' ') {
+                anyhow::bail!("octet-count prefix not terminated by a space");
+            }
+            let len: usize = std::str::from_utf8(&len_buf)?.trim_end().parse()?;
+            if len > MAX_FRAME_BYTES {
+                anyhow::bail!("octet-counted frame of {} bytes exceeds limit", len);
+            }
+            frame.resize(len, 0);
+            reader.read_exact(&mut frame).await?;
+        } else if first == b'\n' || first == b'\r' || first == 0 {
+            reader.consume(1);
+            continue;
+        } else {
+            let n = (&mut reader)
+                .take(MAX_FRAME_BYTES as u64)
+                .read_until(b'\n', &mut frame)
+                .await?;
+            if n == 0 {
+                return Ok(());
+            }
+        }
+
+        let raw = String::from_utf8_lossy(&frame);
+        if let Some(event) = to_event(&raw, &peer) {
+            if tx.send((app_id.clone(), event)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn to_event(raw: &str, peer: &SocketAddr) -> Option<IngestEvent> {
+    match parse_syslog_message(raw) {
+        Some(msg) => Some(msg.into_ingest_event(&peer.ip().to_string())),
+        None => {
+            tracing::debug!("Dropping malformed syslog message from {}", peer);
+            None
+        }
+    }
+}
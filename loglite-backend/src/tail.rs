@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use chrono::{FixedOffset, Utc};
 use globset::{Glob, GlobSetBuilder};
 use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
@@ -11,8 +11,11 @@ use tokio::time::sleep;
 use walkdir::WalkDir;
 
 use crate::entities::prelude::*;
+use crate::handlers::resolve_app;
 use crate::models::IngestEvent;
+use crate::quota::QuotaExceeded;
 use crate::state::AppState;
+use crate::store::accept_events;
 use crate::utils::{detect_log_format, level_to_severity, merge_multiline_logs, LogFormat};
 
 /// Configuration for a single tail source
@@ -25,6 +28,8 @@ pub struct TailSourceConfig {
     pub encoding: String,
     pub include_glob: Option<String>,
     pub exclude_glob: Option<String>,
+    /// Parser for the source's lines; the format is detected without one.
+    pub parser: Option<String>,
 }
 
 /// Tail ingestion manager
@@ -60,6 +65,7 @@ impl TailManager {
                 encoding: s.encoding,
                 include_glob: s.include_glob,
                 exclude_glob: s.exclude_glob,
+                parser: s.parser,
             })
             .collect();
 
@@ -150,6 +156,7 @@ impl TailManager {
             .as_ref()
             .map(|r| r.offset_bytes as u64)
             .unwrap_or(0);
+        let mut generation = offset_record.as_ref().map(|r| r.generation).unwrap_or(0);
 
         // Open file and seek to offset
         let mut file = File::open(file_path)?;
@@ -164,6 +171,7 @@ impl TailManager {
                 file_path
             );
             current_offset = 0;
+            generation += 1;
         }
 
         file.seek(SeekFrom::Start(current_offset))?;
@@ -191,9 +199,23 @@ impl TailManager {
             return Ok(());
         }
 
-        // Detect log format
+        // Use the source's parser, or detect the log format
         let line_refs: Vec<&str> = lines.iter().map(|s| s.as_str()).collect();
-        let format = detect_log_format(&line_refs);
+        let parser = config.parser.as_deref().and_then(|name| {
+            let parser = self.state.parsers.get(name);
+            if parser.is_none() {
+                tracing::warn!(
+                    "Parser {} of source {} not found, detecting the format",
+                    name,
+                    config.source_id
+                );
+            }
+            parser
+        });
+        let format = match parser {
+            Some(parser) => LogFormat::Custom(parser),
+            None => detect_log_format(&line_refs, &self.state.parsers.all()),
+        };
 
         // Parse logs
         let log_entries = if format == LogFormat::Unknown {
@@ -211,86 +233,86 @@ impl TailManager {
                 })
                 .collect()
         } else {
-            merge_multiline_logs(line_refs, format)
+            merge_multiline_logs(line_refs, &format)
         };
 
         // Convert to IngestEvent
         let source_name = file_path.file_name().unwrap_or_default().to_string_lossy();
         let mut events: Vec<IngestEvent> = Vec::new();
 
-        for entry in log_entries {
+        // Keyed on file, truncation generation and read offset, so a chunk re-read after a
+        // failed offset update is not stored twice while new content written after a
+        // truncation is not mistaken for the old.
+        for (i, entry) in log_entries.into_iter().enumerate() {
             events.push(IngestEvent {
                 ts: entry.timestamp,
                 host: String::new(),
                 source: source_name.to_string(),
-                sourcetype: Some(format!("{:?}", format).to_lowercase()),
+                sourcetype: Some(format.name().to_string()),
                 severity: level_to_severity(&entry.level),
                 message: entry.message,
                 fields: entry.fields,
+                idempotency_key: Some(format!(
+                    "{}:{}:{}:{}",
+                    file_path_str, generation, current_offset, i
+                )),
             });
         }
 
-        // Ingest events
+        // Ingest events; an app over its quota pauses the file at its current offset, so the
+        // same lines are read again once the quota allows.
         if !events.is_empty() {
-            self.ingest_events(&config.app_id, events).await?;
+            if let Err(e) = self
+                .ingest_events(&config.app_id, config.source_id, events)
+                .await
+            {
+                if let Some(exceeded) = e.downcast_ref::<QuotaExceeded>() {
+                    tracing::info!("Pausing tail of {:?}: {}", file_path, exceeded);
+                    return Ok(());
+                }
+                return Err(e);
+            }
         }
 
         // Update offset
-        self.update_offset(config.source_id, &file_path_str, new_offset as i64)
-            .await?;
+        self.update_offset(
+            config.source_id,
+            &file_path_str,
+            new_offset as i64,
+            generation,
+        )
+        .await?;
 
         Ok(())
     }
 
-    /// Ingest events into database and search index
-    async fn ingest_events(&self, app_id: &str, events: Vec<IngestEvent>) -> Result<()> {
-        use tantivy::doc;
-
-        let db = &self.state.db;
-        let mut docs: Vec<tantivy::TantivyDocument> = Vec::with_capacity(events.len());
-
-        for e in events {
-            let inserted = crate::entities::events::ActiveModel {
-                id: Set(self.state.ids.next_id()),
-                app_id: Set(app_id.to_string()),
-                ts: Set(e.ts),
-                host: Set(e.host.clone()),
-                source: Set(e.source.clone()),
-                sourcetype: Set(e.sourcetype.clone()),
-                severity: Set(e.severity),
-                message: Set(e.message.clone()),
-                fields: Set(e.fields.clone()),
-            }
-            .insert(db.as_ref())
-            .await?;
-
-            let ts_epoch_ms = inserted.ts.timestamp_millis();
-
-            docs.push(doc!(
-                self.state.search.field_app_id => inserted.app_id.clone(),
-                self.state.search.field_event_id => inserted.id,
-                self.state.search.field_ts_epoch_ms => ts_epoch_ms,
-                self.state.search.field_host => inserted.host,
-                self.state.search.field_source => inserted.source,
-                self.state.search.field_message => inserted.message
-            ));
-        }
-
-        {
-            let mut writer = self.state.search.writer.lock();
-            for d in docs {
-                writer.add_document(d)?;
-            }
-            writer.commit()?;
-        }
-
-        self.state.search.reader.reload()?;
-
+    /// Ingest events into database and search index.
+    ///
+    /// The source's app goes through the unknown-app policy; when it is rejected the file
+    /// offset is not advanced, so tailing resumes once the app exists.
+    async fn ingest_events(
+        &self,
+        app_id: &str,
+        source_id: i64,
+        events: Vec<IngestEvent>,
+    ) -> Result<()> {
+        let app = resolve_app(&self.state, app_id)
+            .await
+            .map_err(|s| anyhow!("cannot ingest into app {}: {}", app_id, s))?;
+        let tagged = app.tag(&events);
+        let events = tagged.as_deref().unwrap_or(&events);
+        accept_events(&self.state, &app.app_id, Some(source_id), events).await?;
         Ok(())
     }
 
     /// Update offset record in database
-    async fn update_offset(&self, source_id: i64, file_path: &str, offset: i64) -> Result<()> {
+    async fn update_offset(
+        &self,
+        source_id: i64,
+        file_path: &str,
+        offset: i64,
+        generation: i64,
+    ) -> Result<()> {
         let existing = TailOffset::find()
             .filter(crate::entities::tail_offsets::Column::SourceId.eq(source_id))
             .filter(crate::entities::tail_offsets::Column::FilePath.eq(file_path))
@@ -303,6 +325,7 @@ impl TailManager {
         if let Some(record) = existing {
             let mut active: crate::entities::tail_offsets::ActiveModel = record.into();
             active.offset_bytes = Set(offset);
+            active.generation = Set(generation);
             active.updated_at = Set(updated_at);
             active.update(self.state.db.as_ref()).await?;
         } else {
@@ -311,6 +334,7 @@ impl TailManager {
                 source_id: Set(source_id),
                 file_path: Set(file_path.to_string()),
                 offset_bytes: Set(offset),
+                generation: Set(generation),
                 updated_at: Set(updated_at),
             }
             .insert(self.state.db.as_ref())
@@ -3,6 +3,10 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use sea_orm::prelude::DateTimeWithTimeZone;
 use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+use crate::access_log::{default_formats, AccessLogFormat};
+use crate::parser::CompiledParser;
 
 /// Generate a stable application id from a display name.
 ///
@@ -37,36 +41,65 @@ fn slugify(s: &str) -> String {
     out.trim_matches('-').to_string()
 }
 
-/// Parse a single nginx access log line.
+/// Parse a single nginx or Apache access log line with `format`, or the default formats.
 ///
-/// Returns (message, fields_json) if successful.
-pub fn parse_nginx_access_line(line: &str) -> Option<(String, serde_json::Value)> {
+/// A line matching no format is kept whole, with only the client address extracted.
+pub fn parse_nginx_access_line(line: &str, format: Option<&AccessLogFormat>) -> Option<LogEntry> {
     let line = line.trim();
     if line.is_empty() {
         return None;
     }
 
-    let mut parts = line.splitn(2, ' ');
-    let remote_addr = parts.next()?.to_string();
-
-    Some((
-        line.to_string(),
-        serde_json::json!({
-            "remote_addr": remote_addr
-        }),
-    ))
+    let parsed = match format {
+        Some(format) => format.parse_line(line),
+        None => default_formats().iter().find_map(|f| f.parse_line(line)),
+    };
+    parsed.or_else(|| {
+        let remote_addr = line.split(' ').next()?;
+        Some(LogEntry {
+            timestamp: Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap()),
+            level: "INFO".to_string(),
+            message: line.to_string(),
+            stacktrace: None,
+            fields: serde_json::json!({
+                "remote_addr": remote_addr
+            }),
+        })
+    })
 }
 
 /// Log format types
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LogFormat {
     Java,
     Rust,
     Go,
+    /// Python `logging` and Celery worker lines, with tracebacks.
+    Python,
     Nginx,
+    /// `key=value` pairs, as emitted by Heroku and many Go services.
+    Logfmt,
+    /// A user-defined parser.
+    Custom(Arc<CompiledParser>),
     Unknown,
 }
 
+impl LogFormat {
+    /// Lowercase name of the format, or the parser's name for custom formats.
+    pub fn name(&self) -> &str {
+        match self {
+            LogFormat::Java => "java",
+            LogFormat::Rust => "rust",
+            LogFormat::Go => "go",
+            LogFormat::Python => "python",
+            LogFormat::Nginx => "nginx",
+            LogFormat::Logfmt => "logfmt",
+            LogFormat::Custom(parser) => &parser.name,
+            LogFormat::Unknown => "unknown",
+        }
+    }
+}
+
 /// Parsed log entry with structured fields
 #[derive(Debug, Clone)]
 pub struct LogEntry {
@@ -93,6 +126,36 @@ lazy_static! {
         r"^(\d{4}/\d{2}/\d{2}\s+\d{2}:\d{2}:\d{2})\s+\[?(ERROR|WARN|INFO|DEBUG|TRACE)?\]?\s*([^:]+)?:?\s*(.+)$"
     ).unwrap();
 
+    // Go crash: "panic: runtime error: ..." or "fatal error: concurrent map writes"
+    static ref GO_PANIC_RE: Regex = Regex::new(r"^(?:panic|fatal error): (.*)$").unwrap();
+
+    // "goroutine 1 [running]:"
+    static ref GO_GOROUTINE_RE: Regex = Regex::new(r"^goroutine (\d+) \[[^\]]*\]:$").unwrap();
+
+    // Rust panic: "thread 'main' panicked at src/main.rs:10:5:", or before Rust 1.73
+    // "thread 'main' panicked at 'message', src/main.rs:10:5"
+    static ref RUST_PANIC_RE: Regex = Regex::new(
+        r"^thread '([^']*)' panicked at (?:'(.*)', )?(\S+?):?$"
+    ).unwrap();
+
+    // Rust backtrace frame: "  12: myapp::handler::run", then "      at ./src/handler.rs:42:9"
+    static ref RUST_FRAME_RE: Regex = Regex::new(r"^\s*\d+: (.+)$").unwrap();
+
+    // Python logging: "2024-02-09 22:30:15,123 - myapp.views - ERROR - Message"
+    static ref PYTHON_LOG_RE: Regex = Regex::new(
+        r"^(\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}[.,]\d{3})\s+-\s+(\S+)\s+-\s+(DEBUG|INFO|WARNING|WARN|ERROR|CRITICAL|FATAL)\s+-\s+(.*)$"
+    ).unwrap();
+
+    // Celery worker: "[2024-02-09 22:30:15,123: ERROR/ForkPoolWorker-1] Message"
+    static ref CELERY_LOG_RE: Regex = Regex::new(
+        r"^\[(\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}[.,]\d{3}):\s+(DEBUG|INFO|WARNING|WARN|ERROR|CRITICAL|FATAL)/([^\]]+)\]\s+(.*)$"
+    ).unwrap();
+
+    // Python traceback lines that only appear in tracebacks
+    static ref PYTHON_TRACEBACK_RE: Regex = Regex::new(
+        r#"^(Traceback \(most recent call last\):|\s+File ".*", line \d+)"#
+    ).unwrap();
+
     // Nginx access log: IP address at start
     static ref NGINX_LOG_RE: Regex = Regex::new(
         r"^\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}"
@@ -105,7 +168,9 @@ lazy_static! {
 }
 
 /// Detect log format from sample lines
-pub fn detect_log_format(lines: &[&str]) -> LogFormat {
+///
+/// The built-in formats are tried first, then `parsers` in order.
+pub fn detect_log_format(lines: &[&str], parsers: &[Arc<CompiledParser>]) -> LogFormat {
     if lines.is_empty() {
         return LogFormat::Unknown;
     }
@@ -114,7 +179,13 @@ pub fn detect_log_format(lines: &[&str]) -> LogFormat {
     let mut java_score = 0;
     let mut rust_score = 0;
     let mut go_score = 0;
+    let mut python_score = 0;
+    let mut in_traceback = false;
+    let mut in_go_panic = false;
+    let mut in_rust_panic = false;
     let mut nginx_score = 0;
+    let mut logfmt_score = 0;
+    let mut custom_scores = vec![0; parsers.len()];
 
     for line in lines.iter().take(sample_size) {
         let line = line.trim();
@@ -125,18 +196,50 @@ pub fn detect_log_format(lines: &[&str]) -> LogFormat {
         if JAVA_LOG_RE.is_match(line) {
             java_score += 1;
         }
+        // As with tracebacks, every line of a panic counts
         if RUST_LOG_RE.is_match(line) {
             rust_score += 1;
+            in_rust_panic = false;
+        } else if RUST_PANIC_RE.is_match(line) {
+            rust_score += 1;
+            in_rust_panic = true;
+        } else if in_rust_panic {
+            rust_score += 1;
         }
         if GO_LOG_RE.is_match(line) {
             go_score += 1;
+            in_go_panic = false;
+        } else if GO_PANIC_RE.is_match(line) {
+            go_score += 1;
+            in_go_panic = true;
+        } else if in_go_panic {
+            go_score += 1;
+        }
+        // Traceback lines count too, or a sample holding one long traceback would not match
+        if PYTHON_LOG_RE.is_match(line) || CELERY_LOG_RE.is_match(line) {
+            python_score += 1;
+            in_traceback = false;
+        } else if PYTHON_TRACEBACK_RE.is_match(line) {
+            python_score += 1;
+            in_traceback = true;
+        } else if in_traceback {
+            python_score += 1;
         }
-        if NGINX_LOG_RE.is_match(line) {
+        if NGINX_LOG_RE.is_match(line) || default_formats().iter().any(|f| f.is_match(line)) {
             nginx_score += 1;
         }
+        if parse_logfmt_pairs(line).is_some() {
+            logfmt_score += 1;
+        }
+        for (parser, score) in parsers.iter().zip(custom_scores.iter_mut()) {
+            if parser.is_match(line) {
+                *score += 1;
+            }
+        }
     }
 
-    let threshold = (sample_size as f32 * 0.6) as usize;
+    // At least one line must match, or a single-line sample would always be Java.
+    let threshold = ((sample_size as f32 * 0.6) as usize).max(1);
 
     if java_score >= threshold {
         LogFormat::Java
@@ -144,8 +247,14 @@ pub fn detect_log_format(lines: &[&str]) -> LogFormat {
         LogFormat::Rust
     } else if go_score >= threshold {
         LogFormat::Go
+    } else if python_score >= threshold {
+        LogFormat::Python
     } else if nginx_score >= threshold {
         LogFormat::Nginx
+    } else if logfmt_score >= threshold {
+        LogFormat::Logfmt
+    } else if let Some(i) = custom_scores.iter().position(|&score| score >= threshold) {
+        LogFormat::Custom(parsers[i].clone())
     } else {
         LogFormat::Unknown
     }
@@ -154,6 +263,7 @@ pub fn detect_log_format(lines: &[&str]) -> LogFormat {
 /// Map log level string to syslog severity
 pub fn level_to_severity(level: &str) -> Option<i32> {
     match level.to_uppercase().as_str() {
+        "CRITICAL" => Some(2),
         "FATAL" | "ERROR" => Some(3),
         "WARN" | "WARNING" => Some(4),
         "INFO" => Some(6),
@@ -163,7 +273,7 @@ pub fn level_to_severity(level: &str) -> Option<i32> {
 }
 
 /// Parse timestamp with multiple format attempts
-fn parse_timestamp(ts_str: &str) -> Option<DateTimeWithTimeZone> {
+pub fn parse_timestamp(ts_str: &str) -> Option<DateTimeWithTimeZone> {
     // Try ISO8601 format first
     if let Ok(dt) = DateTime::parse_from_rfc3339(ts_str) {
         return Some(dt.with_timezone(&FixedOffset::east_opt(0).unwrap()));
@@ -309,11 +419,270 @@ pub fn parse_go_log_line(line: &str) -> Option<LogEntry> {
     })
 }
 
+/// Start of a Go or Rust panic: an `ERROR` entry with the panic message, when the header
+/// carries it, in `fields.panic`. The rest is read from the whole block by [`describe_panic`].
+fn parse_panic_line(line: &str, format: &LogFormat, ts: DateTimeWithTimeZone) -> Option<LogEntry> {
+    let message = match format {
+        LogFormat::Go => GO_PANIC_RE.captures(line)?.get(1)?.as_str(),
+        LogFormat::Rust => RUST_PANIC_RE
+            .captures(line)?
+            .get(2)
+            .map_or("", |m| m.as_str()),
+        _ => return None,
+    };
+    Some(LogEntry {
+        timestamp: ts,
+        level: "ERROR".to_string(),
+        message: message.to_string(),
+        stacktrace: None,
+        fields: serde_json::json!({
+            "panic": message,
+        }),
+    })
+}
+
+/// Fill a panic entry from its whole block: the goroutine or thread, the message of a Rust
+/// panic printed below its header, and the top frame outside the runtime and standard library
+/// as `frame` and `frame_file`.
+fn describe_panic(entry: &mut LogEntry, lines: &[String], format: &LogFormat) {
+    let mut frame = None;
+    match format {
+        LogFormat::Go => {
+            let Some(start) = lines.iter().position(|l| GO_GOROUTINE_RE.is_match(l)) else {
+                return;
+            };
+            if let Some(caps) = GO_GOROUTINE_RE.captures(&lines[start]) {
+                entry.fields["goroutine"] = serde_json::json!(caps[1].parse::<u64>().ok());
+            }
+            // Frames are a call line followed by a tab-indented "file:line +0x..." line
+            let frames = lines[start + 1..]
+                .windows(2)
+                .take_while(|pair| !pair[0].is_empty() && !GO_GOROUTINE_RE.is_match(&pair[0]))
+                .filter(|pair| !pair[0].starts_with('\t') && pair[1].starts_with('\t'));
+            let mut first = None;
+            for pair in frames {
+                let function = pair[0]
+                    .rfind('(')
+                    .map_or(pair[0].as_str(), |i| &pair[0][..i]);
+                let file = pair[1].trim();
+                let file = file.split(" +0x").next().unwrap_or(file);
+                first.get_or_insert((function, file));
+                if !function.starts_with("runtime.") && !function.starts_with("panic") {
+                    frame = Some((function, file));
+                    break;
+                }
+            }
+            frame = frame.or(first);
+        }
+        LogFormat::Rust => {
+            let Some(caps) = lines.first().and_then(|l| RUST_PANIC_RE.captures(l)) else {
+                return;
+            };
+            entry.fields["thread"] = serde_json::json!(&caps[1]);
+            entry.fields["location"] = serde_json::json!(&caps[3]);
+            if caps.get(2).is_none() {
+                let message = lines[1..]
+                    .iter()
+                    .take_while(|l| !l.starts_with("note: ") && !l.starts_with("stack backtrace:"))
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                entry.fields["panic"] = serde_json::json!(message);
+                entry.message = message;
+            }
+
+            const INTERNAL: &[&str] = &[
+                "std::",
+                "core::",
+                "alloc::",
+                "<std::",
+                "<core::",
+                "<alloc::",
+                "rust_begin_unwind",
+                "__rust",
+                "__libc",
+                "_start",
+            ];
+            for (i, line) in lines.iter().enumerate() {
+                let Some(caps) = RUST_FRAME_RE.captures(line) else {
+                    continue;
+                };
+                let function = caps.get(1).map_or("", |m| m.as_str());
+                if INTERNAL.iter().any(|prefix| function.starts_with(prefix)) {
+                    continue;
+                }
+                let file = lines
+                    .get(i + 1)
+                    .and_then(|l| l.trim_start().strip_prefix("at "))
+                    .unwrap_or("");
+                frame = Some((function, file));
+                break;
+            }
+        }
+        _ => {}
+    }
+
+    if let Some((function, file)) = frame {
+        entry.fields["frame"] = serde_json::json!(function);
+        if !file.is_empty() {
+            entry.fields["frame_file"] = serde_json::json!(file);
+        }
+    }
+}
+
+/// Parse a Python `logging` line in the default `%(asctime)s - %(name)s - %(levelname)s -
+/// %(message)s` layout, or a Celery worker line.
+pub fn parse_python_log_line(line: &str) -> Option<LogEntry> {
+    if let Some(caps) = PYTHON_LOG_RE.captures(line) {
+        return Some(LogEntry {
+            timestamp: parse_timestamp(caps.get(1)?.as_str())?,
+            level: caps.get(3)?.as_str().to_string(),
+            message: caps.get(4)?.as_str().to_string(),
+            stacktrace: None,
+            fields: serde_json::json!({
+                "logger": caps.get(2)?.as_str(),
+            }),
+        });
+    }
+
+    let caps = CELERY_LOG_RE.captures(line)?;
+    Some(LogEntry {
+        timestamp: parse_timestamp(caps.get(1)?.as_str())?,
+        level: caps.get(2)?.as_str().to_string(),
+        message: caps.get(4)?.as_str().to_string(),
+        stacktrace: None,
+        fields: serde_json::json!({
+            "process": caps.get(3)?.as_str(),
+        }),
+    })
+}
+
+/// Split a logfmt line into its keys and values; a bare key has no value.
+///
+/// Values may be double-quoted with backslash escapes. Returns `None` unless the line has at
+/// least two `key=value` pairs, or consists only of them, so plain text is not mistaken for
+/// logfmt.
+pub fn parse_logfmt_pairs(line: &str) -> Option<Vec<(String, Option<String>)>> {
+    let mut pairs = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+
+    while chars.peek().is_some() {
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c.is_whitespace() {
+                break;
+            }
+            if c == '"' {
+                return None;
+            }
+            key.push(c);
+            chars.next();
+        }
+        if key.is_empty() {
+            return None;
+        }
+
+        let value = if chars.peek() == Some(&'=') {
+            chars.next();
+            let mut value = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            'n' => value.push('\n'),
+                            't' => value.push('\t'),
+                            'r' => value.push('\r'),
+                            other => value.push(other),
+                        },
+                        c => value.push(c),
+                    }
+                }
+                if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                    return None;
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+            }
+            Some(value)
+        } else {
+            None
+        };
+        pairs.push((key, value));
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    let with_values = pairs.iter().filter(|(_, v)| v.is_some()).count();
+    (with_values >= 2 || (with_values == pairs.len() && with_values > 0)).then_some(pairs)
+}
+
+/// Parse a logfmt line.
+///
+/// `ts`, `time` or `timestamp`, `level` or `lvl`, and `msg` or `message` fill the entry; the
+/// remaining keys go to `fields`, with bare keys as `true`. Without a message the whole line
+/// is the message, and without a parseable timestamp the current time is used.
+pub fn parse_logfmt_line(line: &str) -> Option<LogEntry> {
+    let mut timestamp = None;
+    let mut level = None;
+    let mut message = None;
+    let mut fields = serde_json::Map::new();
+
+    for (key, value) in parse_logfmt_pairs(line)? {
+        match (key.as_str(), value) {
+            ("ts" | "time" | "timestamp", Some(v)) if timestamp.is_none() => {
+                timestamp = parse_timestamp(&v).or_else(|| {
+                    // Epoch seconds, as written by some loggers
+                    let secs = v.parse::<f64>().ok()?;
+                    let utc = DateTime::from_timestamp(secs as i64, (secs.fract() * 1e9) as u32)?;
+                    Some(utc.with_timezone(&FixedOffset::east_opt(0).unwrap()))
+                });
+                if timestamp.is_none() {
+                    fields.insert(key, serde_json::Value::String(v));
+                }
+            }
+            ("level" | "lvl", Some(v)) if level.is_none() => level = Some(v),
+            ("msg" | "message", Some(v)) if message.is_none() => message = Some(v),
+            (_, Some(v)) => {
+                fields.insert(key, serde_json::Value::String(v));
+            }
+            (_, None) => {
+                fields.insert(key, serde_json::Value::Bool(true));
+            }
+        }
+    }
+
+    Some(LogEntry {
+        timestamp: timestamp
+            .unwrap_or_else(|| Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap())),
+        level: level.unwrap_or_else(|| "INFO".to_string()),
+        message: message.unwrap_or_else(|| line.trim().to_string()),
+        stacktrace: None,
+        fields: serde_json::Value::Object(fields),
+    })
+}
+
 /// Merge multi-line logs (e.g., Java stack traces)
-pub fn merge_multiline_logs(lines: Vec<&str>, format: LogFormat) -> Vec<LogEntry> {
+///
+/// A Go or Rust panic becomes one `ERROR` entry holding the whole block in `stacktrace`; it
+/// has no timestamp of its own, so it takes the one of the entry before it.
+pub fn merge_multiline_logs(lines: Vec<&str>, format: &LogFormat) -> Vec<LogEntry> {
     let mut entries = Vec::new();
     let mut current_entry: Option<LogEntry> = None;
     let mut stacktrace_lines: Vec<String> = Vec::new();
+    // Python tracebacks run from "Traceback ..." to the next record, exception line included
+    let mut in_traceback = false;
+    // Go and Rust panics run from their first line to the next record
+    let mut in_panic = false;
 
     for line in lines {
         let line = line.trim_end();
@@ -324,6 +693,18 @@ pub fn merge_multiline_logs(lines: Vec<&str>, format: LogFormat) -> Vec<LogEntry
         // Check if this is a continuation line (stack trace)
         let is_continuation = match format {
             LogFormat::Java => JAVA_STACKTRACE_RE.is_match(line),
+            LogFormat::Python if current_entry.is_some() => {
+                if line.starts_with("Traceback (most recent call last):") {
+                    in_traceback = true;
+                }
+                in_traceback && !PYTHON_LOG_RE.is_match(line) && !CELERY_LOG_RE.is_match(line)
+            }
+            LogFormat::Go => {
+                in_panic && !GO_PANIC_RE.is_match(line) && parse_go_log_line(line).is_none()
+            }
+            LogFormat::Rust => {
+                in_panic && !RUST_PANIC_RE.is_match(line) && !RUST_LOG_RE.is_match(line)
+            }
             _ => false,
         };
 
@@ -332,7 +713,11 @@ pub fn merge_multiline_logs(lines: Vec<&str>, format: LogFormat) -> Vec<LogEntry
             stacktrace_lines.push(line.to_string());
         } else {
             // This is a new log entry, save the previous one
+            in_traceback = false;
             if let Some(mut entry) = current_entry.take() {
+                if in_panic {
+                    describe_panic(&mut entry, &stacktrace_lines, format);
+                }
                 if !stacktrace_lines.is_empty() {
                     entry.stacktrace = Some(stacktrace_lines.join("\n"));
                     entry.fields["stacktrace"] =
@@ -341,12 +726,28 @@ pub fn merge_multiline_logs(lines: Vec<&str>, format: LogFormat) -> Vec<LogEntry
                 }
                 entries.push(entry);
             }
+            in_panic = false;
+
+            let ts = entries.last().map_or_else(
+                || Utc::now().with_timezone(&FixedOffset::east_opt(0).unwrap()),
+                |e: &LogEntry| e.timestamp,
+            );
+            if let Some(entry) = parse_panic_line(line, format, ts) {
+                stacktrace_lines.push(line.to_string());
+                current_entry = Some(entry);
+                in_panic = true;
+                continue;
+            }
 
             // Try to parse the new entry
             let parsed = match format {
                 LogFormat::Java => parse_java_log_line(line),
                 LogFormat::Rust => parse_rust_log_line(line),
                 LogFormat::Go => parse_go_log_line(line),
+                LogFormat::Python => parse_python_log_line(line),
+                LogFormat::Nginx => parse_nginx_access_line(line, None),
+                LogFormat::Logfmt => parse_logfmt_line(line),
+                LogFormat::Custom(parser) => parser.parse_line(line),
                 _ => None,
             };
 
@@ -374,6 +775,9 @@ pub fn merge_multiline_logs(lines: Vec<&str>, format: LogFormat) -> Vec<LogEntry
 
     // Don't forget the last entry
     if let Some(mut entry) = current_entry {
+        if in_panic {
+            describe_panic(&mut entry, &stacktrace_lines, format);
+        }
         if !stacktrace_lines.is_empty() {
             entry.stacktrace = Some(stacktrace_lines.join("\n"));
             entry.fields["stacktrace"] = serde_json::Value::String(stacktrace_lines.join("\n"));
@@ -0,0 +1,618 @@
+use anyhow::Result;
+use sea_orm::{DbErr, RuntimeErr};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::models::{IngestEvent, WalReplay, WalStats};
+use crate::state::AppState;
+use crate::store::write_events;
+
+/// Record header: payload length, event count and CRC32 of the payload, all little-endian u32.
+const RECORD_HEADER_BYTES: u64 = 12;
+
+/// Upper bound on events written to the database per drain step.
+const DRAIN_BATCH_EVENTS: usize = 5000;
+
+const CURSOR_FILE: &str = "cursor";
+
+/// Subdirectory holding batches the database rejected.
+const DEAD_LETTER_DIR: &str = "dead-letter";
+
+/// WAL configuration, read from `LOGLITE_WAL_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct WalConfig {
+    pub enabled: bool,
+    pub dir: String,
+    pub max_bytes: u64,
+    pub segment_bytes: u64,
+    /// Rejected drain attempts before a batch is moved to the dead-letter directory.
+    pub max_attempts: u32,
+}
+
+impl WalConfig {
+    /// Load the WAL configuration from the environment.
+    pub fn from_env() -> Self {
+        let bytes = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            enabled: std::env::var("LOGLITE_WAL_ENABLED")
+                .map(|v| !matches!(v.trim(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            dir: std::env::var("LOGLITE_WAL_DIR").unwrap_or_else(|_| "loglite-wal".to_string()),
+            max_bytes: bytes("LOGLITE_WAL_MAX_BYTES", 1024 * 1024 * 1024),
+            segment_bytes: bytes("LOGLITE_WAL_SEGMENT_BYTES", 64 * 1024 * 1024),
+            max_attempts: std::env::var("LOGLITE_WAL_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(3),
+        }
+    }
+}
+
+/// Returned by [`Wal::append`] when the queue has reached `LOGLITE_WAL_MAX_BYTES`.
+#[derive(Debug)]
+pub struct WalFull;
+
+impl std::fmt::Display for WalFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ingestion queue is full")
+    }
+}
+
+impl std::error::Error for WalFull {}
+
+#[derive(Serialize)]
+struct RecordRef<'a> {
+    app_id: &'a str,
+    events: &'a [IngestEvent],
+}
+
+#[derive(Deserialize)]
+struct Record {
+    app_id: String,
+    events: Vec<IngestEvent>,
+}
+
+/// Drain position: segment sequence number and byte offset within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    seq: u64,
+    offset: u64,
+}
+
+/// Records read by the drainer for a single app.
+struct DrainBatch {
+    app_id: String,
+    events: Vec<IngestEvent>,
+    /// Records, event count (from record headers) and bytes consumed, including corrupt
+    /// records that were skipped.
+    records: u64,
+    counted_events: u64,
+    bytes: u64,
+    /// Cursor just past the consumed records.
+    next: Cursor,
+}
+
+struct ActiveSegment {
+    seq: u64,
+    file: File,
+    len: u64,
+}
+
+/// Append-only, segmented write-ahead log that buffers accepted events on disk until they
+/// are written to Postgres and the search index.
+///
+/// Each record holds one ingest batch for one app and is fsynced before `append` returns.
+/// Delivery to the database is at-least-once: a crash between a database write and the
+/// cursor update replays that batch on startup. A batch the database keeps rejecting is set
+/// aside in the `dead-letter` subdirectory so it does not block the records behind it, and
+/// can be re-queued with [`Wal::replay_dead_letters`].
+pub struct Wal {
+    dir: PathBuf,
+    max_bytes: u64,
+    segment_bytes: u64,
+    max_attempts: u32,
+    active: parking_lot::Mutex<ActiveSegment>,
+    appended: Notify,
+    queued_records: AtomicU64,
+    queued_events: AtomicU64,
+    queued_bytes: AtomicU64,
+    drained_events: AtomicU64,
+    dead_lettered_events: AtomicU64,
+    /// Held while dead-lettered batches are re-queued, so each is appended once.
+    replaying: parking_lot::Mutex<()>,
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{:020}.wal", seq))
+}
+
+/// Sequence numbers of the segments in `dir`, oldest first.
+fn list_segments(dir: &Path) -> Result<Vec<u64>> {
+    let mut seqs: Vec<u64> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            name.strip_suffix(".wal")?.parse::<u64>().ok()
+        })
+        .collect();
+    seqs.sort_unstable();
+    Ok(seqs)
+}
+
+fn read_cursor(dir: &Path) -> Option<Cursor> {
+    let text = fs::read_to_string(dir.join(CURSOR_FILE)).ok()?;
+    let (seq, offset) = text.trim().split_once(' ')?;
+    Some(Cursor {
+        seq: seq.parse().ok()?,
+        offset: offset.parse().ok()?,
+    })
+}
+
+/// Persist the cursor atomically (write, fsync, rename).
+fn write_cursor(dir: &Path, cursor: Cursor) -> Result<()> {
+    let tmp = dir.join(format!("{}.tmp", CURSOR_FILE));
+    {
+        let mut file = File::create(&tmp)?;
+        writeln!(file, "{} {}", cursor.seq, cursor.offset)?;
+        file.sync_all()?;
+    }
+    fs::rename(tmp, dir.join(CURSOR_FILE))?;
+    Ok(())
+}
+
+/// Encode a record: header followed by the payload.
+fn encode_record(payload: &[u8], count: u64) -> Vec<u8> {
+    let mut record = Vec::with_capacity(RECORD_HEADER_BYTES as usize + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&(count as u32).to_le_bytes());
+    record.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    record.extend_from_slice(payload);
+    record
+}
+
+/// Payload and event count of an encoded record, if it is complete and its CRC matches.
+fn decode_record(bytes: &[u8]) -> Option<(&[u8], u64)> {
+    let header = bytes.get(..RECORD_HEADER_BYTES as usize)?;
+    let word =
+        |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+    let payload = bytes.get(RECORD_HEADER_BYTES as usize..)?;
+    (payload.len() == word(0) as usize && crc32fast::hash(payload) == word(8))
+        .then_some((payload, word(4) as u64))
+}
+
+/// Whether a drain failure is the database rejecting the data itself (a Postgres data
+/// exception or integrity violation), which retrying cannot fix, rather than a connection or
+/// other transient failure.
+fn is_permanent(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| match cause.downcast_ref::<DbErr>() {
+        Some(DbErr::Exec(RuntimeErr::SqlxError(e)) | DbErr::Query(RuntimeErr::SqlxError(e))) => e
+            .as_database_error()
+            .and_then(|db| db.code())
+            .is_some_and(|code| code.starts_with("22") || code.starts_with("23")),
+        _ => false,
+    })
+}
+
+fn read_header(file: &mut File) -> Result<(u64, u64, u32)> {
+    let mut header = [0u8; RECORD_HEADER_BYTES as usize];
+    file.read_exact(&mut header)?;
+    let word =
+        |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+    Ok((word(0) as u64, word(4) as u64, word(8)))
+}
+
+fn open_segment(dir: &Path, seq: u64) -> Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(segment_path(dir, seq))?;
+    // Make the new directory entry durable along with the data.
+    File::open(dir)?.sync_all()?;
+    Ok(file)
+}
+
+impl Wal {
+    /// Open the WAL directory, dropping segments that were already drained and counting the
+    /// records still queued. Appends always go to a fresh segment, so a record torn by a crash
+    /// only ever sits at the end of a sealed segment.
+    pub fn open(config: &WalConfig) -> Result<Self> {
+        let dir = PathBuf::from(&config.dir);
+        fs::create_dir_all(&dir)?;
+
+        let segments = list_segments(&dir)?;
+        let cursor = read_cursor(&dir).unwrap_or(Cursor {
+            seq: segments.first().copied().unwrap_or(0),
+            offset: 0,
+        });
+
+        let (mut records, mut events, mut bytes) = (0u64, 0u64, 0u64);
+        for &seq in &segments {
+            if seq < cursor.seq {
+                fs::remove_file(segment_path(&dir, seq))?;
+                continue;
+            }
+
+            let mut file = File::open(segment_path(&dir, seq))?;
+            let end = file.metadata()?.len();
+            let mut offset = if seq == cursor.seq { cursor.offset } else { 0 };
+            while offset + RECORD_HEADER_BYTES <= end {
+                file.seek(SeekFrom::Start(offset))?;
+                let (len, count, _) = read_header(&mut file)?;
+                if offset + RECORD_HEADER_BYTES + len > end {
+                    break;
+                }
+                offset += RECORD_HEADER_BYTES + len;
+                records += 1;
+                events += count;
+                bytes += RECORD_HEADER_BYTES + len;
+            }
+        }
+
+        let seq = segments
+            .last()
+            .copied()
+            .unwrap_or(cursor.seq)
+            .max(cursor.seq)
+            + 1;
+        let file = open_segment(&dir, seq)?;
+
+        if records > 0 {
+            tracing::info!("Replaying {} queued events from the ingest WAL", events);
+        }
+
+        Ok(Self {
+            dir,
+            max_bytes: config.max_bytes,
+            segment_bytes: config.segment_bytes,
+            max_attempts: config.max_attempts,
+            active: parking_lot::Mutex::new(ActiveSegment { seq, file, len: 0 }),
+            appended: Notify::new(),
+            queued_records: AtomicU64::new(records),
+            queued_events: AtomicU64::new(events),
+            queued_bytes: AtomicU64::new(bytes),
+            drained_events: AtomicU64::new(0),
+            dead_lettered_events: AtomicU64::new(0),
+            replaying: parking_lot::Mutex::new(()),
+        })
+    }
+
+    /// Durably queue a batch of events for `app_id`.
+    ///
+    /// Returns once the record is fsynced. Fails with [`WalFull`] when the queued bytes would
+    /// exceed the configured limit.
+    pub async fn append(self: &Arc<Self>, app_id: &str, events: &[IngestEvent]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let payload = serde_json::to_vec(&RecordRef { app_id, events })?;
+        let count = events.len() as u64;
+        let wal = self.clone();
+        tokio::task::spawn_blocking(move || wal.append_blocking(&payload, count)).await??;
+
+        self.appended.notify_one();
+        Ok(())
+    }
+
+    fn append_blocking(&self, payload: &[u8], count: u64) -> Result<()> {
+        let size = RECORD_HEADER_BYTES + payload.len() as u64;
+        // Reserve the space up front so concurrent appends cannot overshoot the limit
+        // together; the reservation is released if the write fails.
+        self.queued_bytes
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+                (queued + size <= self.max_bytes).then_some(queued + size)
+            })
+            .map_err(|_| WalFull)?;
+
+        let result = self.write_record(&encode_record(payload, count), size);
+        match &result {
+            Ok(()) => {
+                self.queued_records.fetch_add(1, Ordering::AcqRel);
+                self.queued_events.fetch_add(count, Ordering::AcqRel);
+            }
+            Err(_) => {
+                self.queued_bytes.fetch_sub(size, Ordering::AcqRel);
+            }
+        }
+        result
+    }
+
+    /// Write and fsync an encoded record to the active segment, rolling over to a new
+    /// segment when it would grow past `segment_bytes`.
+    fn write_record(&self, record: &[u8], size: u64) -> Result<()> {
+        let mut active = self.active.lock();
+        if active.len > 0 && active.len + size > self.segment_bytes {
+            let seq = active.seq + 1;
+            *active = ActiveSegment {
+                seq,
+                file: open_segment(&self.dir, seq)?,
+                len: 0,
+            };
+        }
+
+        if let Err(e) = active
+            .file
+            .write_all(record)
+            .and_then(|_| active.file.sync_data())
+        {
+            // Drop any partial record so later appends stay readable.
+            let _ = active.file.set_len(active.len);
+            return Err(e.into());
+        }
+        active.len += size;
+        Ok(())
+    }
+
+    /// Move a batch that could not be drained to the dead-letter directory, as one record in
+    /// a file named after its position in the WAL.
+    fn dead_letter(&self, at: Cursor, batch: &DrainBatch) -> Result<()> {
+        let dir = self.dir.join(DEAD_LETTER_DIR);
+        fs::create_dir_all(&dir)?;
+        let payload = serde_json::to_vec(&RecordRef {
+            app_id: &batch.app_id,
+            events: &batch.events,
+        })?;
+        let path = dir.join(format!("{:020}-{:020}.wal", at.seq, at.offset));
+        let mut file = File::create(path)?;
+        file.write_all(&encode_record(&payload, batch.events.len() as u64))?;
+        file.sync_all()?;
+        File::open(&dir)?.sync_all()?;
+        Ok(())
+    }
+
+    /// Re-queue dead-lettered batches, oldest first, deleting each file once its record is
+    /// appended. Stops at the first batch that does not fit in the queue; unreadable files are
+    /// left in place.
+    pub fn replay_dead_letters(&self) -> Result<WalReplay> {
+        let _replaying = self.replaying.lock();
+        let dir = self.dir.join(DEAD_LETTER_DIR);
+        let mut files: Vec<PathBuf> = match fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "wal"))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        files.sort();
+
+        let mut replay = WalReplay {
+            replayed_batches: 0,
+            replayed_events: 0,
+            remaining_batches: files.len() as u64,
+        };
+        for path in files {
+            let bytes = fs::read(&path)?;
+            let Some((payload, count)) = decode_record(&bytes)
+                .filter(|(payload, _)| serde_json::from_slice::<Record>(payload).is_ok())
+            else {
+                tracing::error!("Skipping unreadable dead-letter file {}", path.display());
+                continue;
+            };
+            match self.append_blocking(payload, count) {
+                Ok(()) => {}
+                Err(e) if e.is::<WalFull>() => break,
+                Err(e) => return Err(e),
+            }
+            fs::remove_file(&path)?;
+            replay.replayed_batches += 1;
+            replay.replayed_events += count;
+            replay.remaining_batches -= 1;
+        }
+
+        if replay.replayed_batches > 0 {
+            File::open(&dir)?.sync_all()?;
+            self.appended.notify_one();
+        }
+        Ok(replay)
+    }
+
+    /// Current queue depth.
+    pub fn stats(&self) -> WalStats {
+        WalStats {
+            enabled: true,
+            queued_records: self.queued_records.load(Ordering::Acquire),
+            queued_events: self.queued_events.load(Ordering::Acquire),
+            queued_bytes: self.queued_bytes.load(Ordering::Acquire),
+            max_bytes: self.max_bytes,
+            drained_events: self.drained_events.load(Ordering::Acquire),
+            dead_lettered_events: self.dead_lettered_events.load(Ordering::Acquire),
+        }
+    }
+
+    /// Readable end of segment `seq`: the committed length for the active segment, the file
+    /// length otherwise. `None` if the segment no longer exists.
+    fn segment_end(&self, seq: u64) -> Option<(u64, bool)> {
+        {
+            let active = self.active.lock();
+            if seq == active.seq {
+                return Some((active.len, true));
+            }
+        }
+        let len = fs::metadata(segment_path(&self.dir, seq)).ok()?.len();
+        Some((len, false))
+    }
+
+    /// Read consecutive records for one app starting at `cursor`, up to the drain budget.
+    ///
+    /// Moves `cursor` past fully drained segments, deleting them.
+    fn read_batch(&self, cursor: &mut Cursor) -> Result<Option<DrainBatch>> {
+        loop {
+            let Some((end, is_active)) = self.segment_end(cursor.seq) else {
+                // Segment gone; continue with the next one on disk.
+                match list_segments(&self.dir)?
+                    .into_iter()
+                    .find(|&s| s > cursor.seq)
+                {
+                    Some(next) => {
+                        *cursor = Cursor {
+                            seq: next,
+                            offset: 0,
+                        };
+                        continue;
+                    }
+                    None => return Ok(None),
+                }
+            };
+
+            let mut file = File::open(segment_path(&self.dir, cursor.seq))?;
+            let mut next = *cursor;
+            let mut app_id: Option<String> = None;
+            let mut events: Vec<IngestEvent> = Vec::new();
+            let (mut records, mut counted_events, mut bytes) = (0u64, 0u64, 0u64);
+
+            while next.offset + RECORD_HEADER_BYTES <= end && events.len() < DRAIN_BATCH_EVENTS {
+                file.seek(SeekFrom::Start(next.offset))?;
+                let (len, count, crc) = read_header(&mut file)?;
+                if next.offset + RECORD_HEADER_BYTES + len > end {
+                    break;
+                }
+                let mut payload = vec![0u8; len as usize];
+                file.read_exact(&mut payload)?;
+
+                let record = if crc32fast::hash(&payload) == crc {
+                    serde_json::from_slice::<Record>(&payload).ok()
+                } else {
+                    None
+                };
+                let Some(record) = record else {
+                    tracing::error!(
+                        "Skipping corrupt WAL record in segment {} at offset {}",
+                        next.seq,
+                        next.offset
+                    );
+                    next.offset += RECORD_HEADER_BYTES + len;
+                    records += 1;
+                    counted_events += count;
+                    bytes += RECORD_HEADER_BYTES + len;
+                    continue;
+                };
+
+                match &app_id {
+                    Some(current) if *current != record.app_id => break,
+                    _ => app_id = Some(record.app_id),
+                }
+                events.extend(record.events);
+                next.offset += RECORD_HEADER_BYTES + len;
+                records += 1;
+                counted_events += count;
+                bytes += RECORD_HEADER_BYTES + len;
+            }
+
+            if records == 0 {
+                if is_active {
+                    return Ok(None);
+                }
+                // Sealed segment fully drained (any trailing bytes are a torn record).
+                fs::remove_file(segment_path(&self.dir, cursor.seq))?;
+                *cursor = Cursor {
+                    seq: cursor.seq + 1,
+                    offset: 0,
+                };
+                write_cursor(&self.dir, *cursor)?;
+                continue;
+            }
+
+            return Ok(Some(DrainBatch {
+                app_id: app_id.unwrap_or_default(),
+                events,
+                records,
+                counted_events,
+                bytes,
+                next,
+            }));
+        }
+    }
+}
+
+/// Background task that drains the WAL into Postgres and the search index.
+///
+/// Batches that fail to persist are retried with capped backoff and stay queued on disk
+/// meanwhile. Connection and other transient failures are retried indefinitely; a batch the
+/// database rejects `LOGLITE_WAL_MAX_ATTEMPTS` times is moved to the dead-letter directory
+/// and draining moves on.
+pub async fn wal_drain_loop(state: Arc<AppState>) {
+    let Some(wal) = state.wal.clone() else {
+        return;
+    };
+
+    let mut cursor = read_cursor(&wal.dir).unwrap_or(Cursor { seq: 0, offset: 0 });
+    let mut backoff = Duration::from_millis(500);
+    let mut attempts: u32 = 0;
+
+    loop {
+        let batch = match wal.read_batch(&mut cursor) {
+            Ok(batch) => batch,
+            Err(e) => {
+                tracing::error!("Failed to read ingest WAL: {}", e);
+                tokio::time::sleep(backoff).await;
+                continue;
+            }
+        };
+
+        let Some(batch) = batch else {
+            let _ = tokio::time::timeout(Duration::from_secs(1), wal.appended.notified()).await;
+            continue;
+        };
+
+        let mut dead_lettered = false;
+        if !batch.events.is_empty() {
+            if let Err(e) = write_events(&state, &batch.app_id, &batch.events).await {
+                if is_permanent(&e) {
+                    attempts += 1;
+                }
+                if attempts < wal.max_attempts {
+                    tracing::warn!("Failed to drain {} WAL events: {}", batch.events.len(), e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(Duration::from_secs(30));
+                    continue;
+                }
+                if let Err(dl) = wal.dead_letter(cursor, &batch) {
+                    tracing::error!("Failed to dead-letter WAL batch: {}", dl);
+                    tokio::time::sleep(backoff).await;
+                    continue;
+                }
+                tracing::error!(
+                    "Moved {} WAL events for app {} to the dead-letter directory after {} rejections: {}",
+                    batch.events.len(),
+                    batch.app_id,
+                    attempts,
+                    e
+                );
+                dead_lettered = true;
+            }
+        }
+        backoff = Duration::from_millis(500);
+        attempts = 0;
+
+        cursor = batch.next;
+        if let Err(e) = write_cursor(&wal.dir, cursor) {
+            tracing::error!("Failed to persist WAL cursor: {}", e);
+        }
+
+        wal.queued_records
+            .fetch_sub(batch.records, Ordering::AcqRel);
+        wal.queued_events
+            .fetch_sub(batch.counted_events, Ordering::AcqRel);
+        wal.queued_bytes.fetch_sub(batch.bytes, Ordering::AcqRel);
+        let drained = if dead_lettered {
+            &wal.dead_lettered_events
+        } else {
+            &wal.drained_events
+        };
+        drained.fetch_add(batch.events.len() as u64, Ordering::AcqRel);
+    }
+}